@@ -0,0 +1,38 @@
+CREATE TABLE checks_series (
+    id SERIAL PRIMARY KEY,
+    request_time_range_start TIMESTAMPTZ NOT NULL,
+    request_time_range_end TIMESTAMPTZ NOT NULL,
+    website VARCHAR NOT NULL,
+    result VARCHAR NOT NULL,
+    latency_count BIGINT NOT NULL DEFAULT 0,
+    latency_sum_ms BIGINT NOT NULL DEFAULT 0,
+    latency_min_ms BIGINT,
+    latency_max_ms BIGINT
+);
+
+CREATE INDEX checks_series_website_end ON checks_series (website, request_time_range_end);
+CREATE INDEX checks_series_website_start ON checks_series (website, request_time_range_start);
+
+CREATE TABLE checks_aggregate (
+    website VARCHAR NOT NULL,
+    resolution VARCHAR NOT NULL,
+    bucket_start TIMESTAMPTZ NOT NULL,
+    ok_millis BIGINT NOT NULL,
+    not_ok_millis BIGINT NOT NULL,
+    ok_series BIGINT NOT NULL,
+    not_ok_series BIGINT NOT NULL,
+    latency_count BIGINT NOT NULL,
+    latency_sum_ms BIGINT NOT NULL,
+    latency_min_ms BIGINT,
+    latency_max_ms BIGINT,
+    UNIQUE (website, resolution, bucket_start)
+);
+
+CREATE INDEX checks_aggregate_website_start ON checks_aggregate (website, bucket_start);
+
+CREATE TABLE websites (
+    slug VARCHAR NOT NULL PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    configured BOOLEAN NOT NULL DEFAULT FALSE,
+    archived BOOLEAN NOT NULL DEFAULT FALSE
+);
@@ -0,0 +1,3 @@
+-- Groups and tags of websites.
+ALTER TABLE websites ADD COLUMN group_name TEXT;
+ALTER TABLE websites ADD COLUMN tags JSONB NOT NULL DEFAULT '[]';
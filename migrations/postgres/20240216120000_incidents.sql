@@ -0,0 +1,21 @@
+-- Incidents reported by hand, affected websites are stored as a JSON array of slugs.
+-- `status` and `updated_at` are copied from the latest update.
+CREATE TABLE incidents (
+    id BIGSERIAL PRIMARY KEY,
+    title TEXT NOT NULL,
+    severity VARCHAR NOT NULL,
+    websites JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL,
+    status VARCHAR NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE incident_updates (
+    id BIGSERIAL PRIMARY KEY,
+    incident_id BIGINT NOT NULL REFERENCES incidents (id),
+    status VARCHAR NOT NULL,
+    message TEXT NOT NULL,
+    time TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX incident_updates_incident ON incident_updates (incident_id);
@@ -0,0 +1,2 @@
+-- Private websites are only shown to authenticated users.
+ALTER TABLE websites ADD COLUMN public BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,4 @@
+-- Websites managed through the API instead of the config, with everything needed to check them.
+ALTER TABLE websites ADD COLUMN managed BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE websites ADD COLUMN url VARCHAR;
+ALTER TABLE websites ADD COLUMN paused BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- Websites are told apart by their name on the status page, so configured and managed ones must not
+-- share it. Unconfigured websites only keep their history and may.
+CREATE UNIQUE INDEX websites_name ON websites (name) WHERE configured OR managed;
@@ -0,0 +1,19 @@
+ALTER TABLE checks_series ADD COLUMN latency_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE checks_series ADD COLUMN latency_sum_ms INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE checks_series ADD COLUMN latency_min_ms INTEGER;
+ALTER TABLE checks_series ADD COLUMN latency_max_ms INTEGER;
+
+CREATE TABLE checks_aggregate (
+    website VARCHAR NOT NULL,
+    resolution VARCHAR NOT NULL,
+    bucket_start TIMESTAMP NOT NULL,
+    ok_millis INTEGER NOT NULL,
+    not_ok_millis INTEGER NOT NULL,
+    ok_series INTEGER NOT NULL,
+    not_ok_series INTEGER NOT NULL,
+    latency_count INTEGER NOT NULL,
+    latency_sum_ms INTEGER NOT NULL,
+    latency_min_ms INTEGER,
+    latency_max_ms INTEGER,
+    UNIQUE (website, resolution, bucket_start)
+);
@@ -0,0 +1,3 @@
+CREATE INDEX checks_series_website_end ON checks_series (website, request_time_range_end);
+CREATE INDEX checks_series_website_start ON checks_series (website, request_time_range_start);
+CREATE INDEX checks_aggregate_website_start ON checks_aggregate (website, bucket_start);
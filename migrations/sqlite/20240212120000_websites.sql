@@ -0,0 +1,12 @@
+CREATE TABLE websites (
+    slug VARCHAR NOT NULL PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    configured BOOLEAN NOT NULL DEFAULT FALSE,
+    archived BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+-- Until now, websites were identified by their name, which becomes their slug.
+INSERT INTO websites (slug, name)
+SELECT website, website FROM checks_series
+UNION
+SELECT website, website FROM checks_aggregate;
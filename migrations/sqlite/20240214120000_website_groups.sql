@@ -0,0 +1,3 @@
+-- Groups and tags of websites, tags are stored as a JSON array.
+ALTER TABLE websites ADD COLUMN group_name VARCHAR;
+ALTER TABLE websites ADD COLUMN tags VARCHAR NOT NULL DEFAULT '[]';
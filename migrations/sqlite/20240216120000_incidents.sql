@@ -0,0 +1,21 @@
+-- Incidents reported by hand, affected websites are stored as a JSON array of slugs.
+-- `status` and `updated_at` are copied from the latest update.
+CREATE TABLE incidents (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    title VARCHAR NOT NULL,
+    severity VARCHAR NOT NULL,
+    websites VARCHAR NOT NULL DEFAULT '[]',
+    created_at TIMESTAMP NOT NULL,
+    status VARCHAR NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE incident_updates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    incident_id INTEGER NOT NULL REFERENCES incidents (id),
+    status VARCHAR NOT NULL,
+    message VARCHAR NOT NULL,
+    time TIMESTAMP NOT NULL
+);
+
+CREATE INDEX incident_updates_incident ON incident_updates (incident_id);
@@ -0,0 +1,3 @@
+-- Time a website was paused is neither OK nor not OK, so rolled up history keeps it apart.
+ALTER TABLE checks_aggregate ADD COLUMN paused_millis INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE checks_aggregate ADD COLUMN paused_series INTEGER NOT NULL DEFAULT 0;
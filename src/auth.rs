@@ -0,0 +1,366 @@
+//! Authentication of requests to the web server, with tokens, HTTP basic auth or a header set by a
+//! trusted reverse proxy. Requests without credentials are anonymous and only see public websites.
+
+use std::net::IpAddr;
+
+use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use axum::{
+    body::Body,
+    extract::{connect_info::Connected, ConnectInfo, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use eyre::{Context, Result};
+use http::{HeaderMap, Request, StatusCode};
+use tokio::sync::watch;
+
+use crate::{
+    config::{AuthConfig, Config, ProxyAuthConfig, Scope},
+    db::Website,
+};
+
+/// Who made a request, added to the extensions of every request.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Identity {
+    /// The name of the token or user, `None` for anonymous requests.
+    pub(crate) name: Option<String>,
+    scopes: Vec<Scope>,
+}
+
+impl Identity {
+    pub(crate) fn has(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    /// Whether private websites are shown.
+    pub(crate) fn private(&self) -> bool {
+        self.has(Scope::Private)
+    }
+
+    pub(crate) fn can_see(&self, website: &Website) -> bool {
+        website.public || self.private()
+    }
+
+    /// Fails with `401 Unauthorized` for anonymous requests and `403 Forbidden` for requests that
+    /// are missing the scope.
+    pub(crate) fn require(&self, scope: Scope) -> Result<(), Response> {
+        if self.has(scope) {
+            Ok(())
+        } else if self.name.is_none() {
+            Err(StatusCode::UNAUTHORIZED.into_response())
+        } else {
+            Err(StatusCode::FORBIDDEN.into_response())
+        }
+    }
+}
+
+/// The IP address of the client, `None` for connections over a Unix domain socket.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Peer(Option<IpAddr>);
+
+impl Connected<&hyper::server::conn::AddrStream> for Peer {
+    fn connect_info(target: &hyper::server::conn::AddrStream) -> Self {
+        Self(Some(target.remote_addr().ip()))
+    }
+}
+
+impl Connected<&tokio::net::UnixStream> for Peer {
+    fn connect_info(_: &tokio::net::UnixStream) -> Self {
+        Self(None)
+    }
+}
+
+/// Adds the [`Identity`] of the request, rejecting it with `401 Unauthorized` if it has invalid
+/// credentials.
+pub(crate) async fn authenticate(
+    State(config): State<watch::Receiver<Config>>,
+    ConnectInfo(peer): ConnectInfo<Peer>,
+    mut request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let auth = config.borrow().auth.clone();
+
+    match identify(&auth, request.headers(), peer).await {
+        Some(identity) => {
+            request.extensions_mut().insert(identity);
+            next.run(request).await
+        }
+        None => {
+            info!(path = %request.uri().path(), "Rejected invalid credentials");
+            challenge(&auth)
+        }
+    }
+}
+
+/// `401 Unauthorized`, asking browsers to log in if there are users.
+pub(crate) fn challenge(auth: &AuthConfig) -> Response {
+    let scheme = if auth.users.is_empty() {
+        "Bearer"
+    } else {
+        r#"Basic realm="uptime", charset="UTF-8""#
+    };
+    (
+        StatusCode::UNAUTHORIZED,
+        [(http::header::WWW_AUTHENTICATE, scheme)],
+    )
+        .into_response()
+}
+
+/// The identity of the request, `None` if its credentials are invalid.
+async fn identify(auth: &AuthConfig, headers: &HeaderMap, peer: Peer) -> Option<Identity> {
+    if let Some(authorization) = headers.get(http::header::AUTHORIZATION) {
+        let authorization = authorization.to_str().ok()?;
+
+        if let Some(token) = authorization.strip_prefix("Bearer ") {
+            let token = auth
+                .tokens
+                .iter()
+                .find(|config| constant_time_eq(config.token.as_bytes(), token.as_bytes()))?;
+            return Some(Identity {
+                name: Some(token.name.clone()),
+                scopes: token.scopes.clone(),
+            });
+        }
+
+        if let Some(credentials) = authorization.strip_prefix("Basic ") {
+            let credentials = base64::engine::general_purpose::STANDARD
+                .decode(credentials)
+                .ok()?;
+            let credentials = String::from_utf8(credentials).ok()?;
+            let (name, password) = credentials.split_once(':')?;
+
+            let user = auth.users.iter().find(|user| user.name == name).cloned();
+            let password = password.to_owned();
+            // Hashing is slow on purpose, so keep it off the async workers.
+            let valid = tokio::task::spawn_blocking(move || match user {
+                Some(user) => verify_password(&password, &user.password_hash).then_some(user),
+                None => {
+                    verify_password(&password, DUMMY_HASH);
+                    None
+                }
+            })
+            .await
+            .ok()??;
+            return Some(Identity {
+                name: Some(valid.name),
+                scopes: valid.scopes,
+            });
+        }
+
+        return None;
+    }
+
+    if let Some(proxy) = &auth.proxy {
+        if let Some(user) = headers.get(&proxy.header) {
+            if is_trusted(proxy, peer) {
+                return Some(Identity {
+                    name: Some(user.to_str().ok()?.to_owned()),
+                    scopes: proxy.scopes.clone(),
+                });
+            }
+        }
+    }
+
+    Some(Identity::default())
+}
+
+fn is_trusted(proxy: &ProxyAuthConfig, peer: Peer) -> bool {
+    // Dual stack sockets report IPv4 clients as IPv4-mapped IPv6 addresses.
+    let canonical = |ip: IpAddr| match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    };
+
+    match peer.0 {
+        Some(ip) => proxy
+            .trusted_proxies
+            .iter()
+            .any(|trusted| canonical(*trusted) == canonical(ip)),
+        None => proxy.trust_unix_socket,
+    }
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    let Ok(hash) = PasswordHash::new(hash) else {
+        return false;
+    };
+    Argon2::default()
+        .verify_password(password.as_bytes(), &hash)
+        .is_ok()
+}
+
+/// Verified for unknown users, so that they take as long to reject as wrong passwords and don't
+/// reveal which users exist. Made with `hash-password`, so it has the same cost as real hashes.
+const DUMMY_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$CpaaMWR+UEcWo/WSmyg47g$LIWIXJ3zA8NDiTBFWANgDg+nQhF4r0PPdW69VhTp79Y";
+
+/// Hashes a password for `password_hash` of a user.
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut argon2::password_hash::rand_core::OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| eyre::eyre!("{err}"))
+        .wrap_err("hashing password")
+}
+
+/// Compares secrets without leaking how much of them matched through the timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use base64::Engine;
+    use http::HeaderMap;
+
+    use super::{constant_time_eq, identify, verify_password, Identity, Peer, DUMMY_HASH};
+    use crate::config::{AuthConfig, ProxyAuthConfig, Scope, TokenConfig, UserConfig};
+
+    const TOKEN: &str = "0123456789abcdef";
+    /// The hash of `hunter2`.
+    const PASSWORD_HASH: &str =
+        "$argon2id$v=19$m=19456,t=2,p=1$ajoh4Xl4JVM7SR31JFmyhg$M+wCz9/wWMN2qK8CXoXPmRqRoOPQxDTqwXxTv5sczMQ";
+
+    fn auth(proxy: Option<ProxyAuthConfig>) -> AuthConfig {
+        AuthConfig {
+            tokens: vec![TokenConfig {
+                name: "ci".into(),
+                token: TOKEN.into(),
+                scopes: vec![Scope::Incidents],
+            }],
+            users: vec![UserConfig {
+                name: "admin".into(),
+                password_hash: PASSWORD_HASH.into(),
+                scopes: vec![Scope::Private],
+            }],
+            proxy,
+        }
+    }
+
+    fn proxy(trust_unix_socket: bool) -> ProxyAuthConfig {
+        ProxyAuthConfig {
+            header: "x-forwarded-user".into(),
+            trusted_proxies: vec!["10.0.0.1".parse().unwrap()],
+            trust_unix_socket,
+            scopes: vec![Scope::Private],
+        }
+    }
+
+    fn headers(headers: &[(&'static str, &str)]) -> HeaderMap {
+        headers
+            .iter()
+            .map(|(name, value)| (http::HeaderName::from_static(name), value.parse().unwrap()))
+            .collect()
+    }
+
+    fn basic(credentials: &str) -> String {
+        format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode(credentials)
+        )
+    }
+
+    fn tcp(ip: &str) -> Peer {
+        Peer(Some(ip.parse::<IpAddr>().unwrap()))
+    }
+
+    /// The name and scopes of the identity, to compare them.
+    fn who(identity: Option<Identity>) -> Option<(Option<String>, Vec<Scope>)> {
+        identity.map(|identity| (identity.name, identity.scopes))
+    }
+
+    #[tokio::test]
+    async fn anonymous_without_credentials() {
+        let identity = identify(&auth(None), &HeaderMap::new(), tcp("192.0.2.1")).await;
+        assert_eq!(who(identity), Some((None, vec![])));
+    }
+
+    #[tokio::test]
+    async fn bearer_tokens() {
+        let auth = auth(None);
+
+        let valid = headers(&[("authorization", &format!("Bearer {TOKEN}"))]);
+        let identity = identify(&auth, &valid, tcp("192.0.2.1")).await;
+        assert_eq!(
+            who(identity),
+            Some((Some("ci".into()), vec![Scope::Incidents]))
+        );
+
+        for token in ["0123456789abcdeX", "0123456789abcde", ""] {
+            let invalid = headers(&[("authorization", &format!("Bearer {token}"))]);
+            assert!(identify(&auth, &invalid, tcp("192.0.2.1")).await.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn basic_auth() {
+        let auth = auth(None);
+
+        let valid = headers(&[("authorization", &basic("admin:hunter2"))]);
+        let identity = identify(&auth, &valid, tcp("192.0.2.1")).await;
+        assert_eq!(
+            who(identity),
+            Some((Some("admin".into()), vec![Scope::Private]))
+        );
+
+        for credentials in ["admin:hunter3", "nobody:hunter2", "admin", ""] {
+            let invalid = headers(&[("authorization", &basic(credentials))]);
+            assert!(identify(&auth, &invalid, tcp("192.0.2.1")).await.is_none());
+        }
+        let malformed = headers(&[("authorization", "Basic not base64!")]);
+        assert!(identify(&auth, &malformed, tcp("192.0.2.1"))
+            .await
+            .is_none());
+    }
+
+    #[test]
+    fn dummy_hash_is_verified() {
+        // Unknown users would be rejected right away with a hash that fails to parse.
+        assert!(argon2::PasswordHash::new(DUMMY_HASH).is_ok());
+        assert!(!verify_password("hunter2", DUMMY_HASH));
+    }
+
+    #[tokio::test]
+    async fn proxy_header_from_trusted_peers() {
+        let auth = auth(Some(proxy(false)));
+        let user = headers(&[("x-forwarded-user", "alice")]);
+        let alice = Some((Some("alice".into()), vec![Scope::Private]));
+
+        let identity = identify(&auth, &user, tcp("10.0.0.1")).await;
+        assert_eq!(who(identity), alice);
+        // Dual stack sockets report IPv4 clients like this.
+        let identity = identify(&auth, &user, tcp("::ffff:10.0.0.1")).await;
+        assert_eq!(who(identity), alice);
+
+        // Anyone else could set the header, so it is ignored.
+        let identity = identify(&auth, &user, tcp("10.0.0.2")).await;
+        assert_eq!(who(identity), Some((None, vec![])));
+        let identity = identify(&auth, &user, Peer(None)).await;
+        assert_eq!(who(identity), Some((None, vec![])));
+    }
+
+    #[tokio::test]
+    async fn proxy_header_over_trusted_unix_socket() {
+        let auth = auth(Some(proxy(true)));
+        let user = headers(&[("x-forwarded-user", "alice")]);
+
+        let identity = identify(&auth, &user, Peer(None)).await;
+        assert_eq!(
+            who(identity),
+            Some((Some("alice".into()), vec![Scope::Private]))
+        );
+    }
+
+    #[test]
+    fn constant_time_eq_compares_contents_and_length() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret!"));
+        assert!(!constant_time_eq(b"", b"secret"));
+        assert!(constant_time_eq(b"", b""));
+    }
+}
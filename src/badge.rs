@@ -0,0 +1,188 @@
+//! SVG badges in the style of shields.io, for READMEs and other status pages.
+
+use askama::Template;
+use chrono::Duration;
+use eyre::{Context, Result};
+
+use crate::{
+    client::CheckState,
+    db::{LatencySummary, Storage, Website},
+    web,
+};
+
+/// The uptime and response time badges cover this many days by default.
+pub(crate) const DEFAULT_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Badge {
+    /// The state of the latest check.
+    Status,
+    /// The ratio of time the website was up.
+    Uptime,
+    /// The average response time.
+    ResponseTime,
+}
+
+impl Badge {
+    pub(crate) const ALL: [Badge; 3] = [Badge::Status, Badge::Uptime, Badge::ResponseTime];
+
+    pub(crate) fn file_name(self) -> &'static str {
+        match self {
+            Self::Status => "status.svg",
+            Self::Uptime => "uptime.svg",
+            Self::ResponseTime => "response-time.svg",
+        }
+    }
+
+    /// The text on the left of the badge.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::Status => "status",
+            Self::Uptime => "uptime",
+            Self::ResponseTime => "response time",
+        }
+    }
+
+    pub(crate) fn from_file_name(file_name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|badge| badge.file_name() == file_name)
+    }
+}
+
+/// Renders a badge of a website from the same status as the status page. The uptime and response
+/// time cover the last `range` of the history.
+pub(crate) async fn render<S: Storage + ?Sized>(
+    db: &S,
+    website: Website,
+    badge: Badge,
+    range: Duration,
+) -> Result<String> {
+    match badge {
+        Badge::Status => {
+            let archived = website.archived;
+            let status = web::website_status(db, website, range).await?;
+            self::status(status.and_then(|status| status.current), archived)
+        }
+        Badge::Uptime => {
+            let status = web::website_status(db, website, range).await?;
+            uptime(status.and_then(|status| status.uptime))
+        }
+        Badge::ResponseTime => {
+            let Some(bounds) = db.get_history_bounds(&website.slug).await? else {
+                return response_time(None);
+            };
+            let range = web::clamp_range(bounds, range);
+
+            let mut latency = LatencySummary::default();
+            for series in db
+                .get_checks_series_in_range(&website.slug, range.clone())
+                .await?
+            {
+                latency.merge(&series.latency);
+            }
+            for aggregate in db
+                .get_checks_aggregates_in_range(&website.slug, range)
+                .await?
+            {
+                latency.merge(&aggregate.latency);
+            }
+            response_time(latency.average_ms())
+        }
+    }
+}
+
+const GREEN: &str = "#4c1";
+const YELLOW_GREEN: &str = "#a4a61d";
+const YELLOW: &str = "#dfb317";
+const RED: &str = "#e05d44";
+const BLUE: &str = "#007ec6";
+const GREY: &str = "#9f9f9f";
+
+#[derive(Template)]
+#[template(path = "badge.svg", escape = "html")]
+struct BadgeTemplate<'a> {
+    label: &'a str,
+    message: &'a str,
+    color: &'a str,
+    label_width: u32,
+    message_width: u32,
+    width: u32,
+}
+
+fn render_svg(label: &str, message: &str, color: &str) -> Result<String> {
+    let label_width = text_width(label) + 10;
+    let message_width = text_width(message) + 10;
+    BadgeTemplate {
+        label,
+        message,
+        color,
+        label_width,
+        message_width,
+        width: label_width + message_width,
+    }
+    .render()
+    .wrap_err("rendering badge")
+}
+
+/// Roughly the width of `text` in 11px Verdana, which is good enough to size the badge.
+fn text_width(text: &str) -> u32 {
+    let width = text
+        .chars()
+        .map(|char| match char {
+            'i' | 'l' | 'j' | '.' | ',' | ':' | ';' | '|' | '!' | '\'' => 3.5,
+            ' ' | 'f' | 'r' | 't' | 'I' | '(' | ')' => 4.5,
+            'm' | 'w' | 'M' | 'W' | '%' => 10.0,
+            _ => 7.0,
+        })
+        .sum::<f32>();
+    width.ceil() as u32
+}
+
+/// The state of the latest check of a website.
+fn status(current: Option<CheckState>, archived: bool) -> Result<String> {
+    let (message, color) = match (archived, current) {
+        (true, _) => ("archived", GREY),
+        (false, Some(CheckState::Ok)) => ("up", GREEN),
+        (false, Some(CheckState::NotOk)) => ("down", RED),
+        (false, Some(CheckState::Paused)) => ("paused", BLUE),
+        (false, None) => ("unknown", GREY),
+    };
+    render_svg(Badge::Status.label(), message, color)
+}
+
+/// The ratio of time a website was up, `None` if there is no history or it was paused.
+fn uptime(uptime: Option<f32>) -> Result<String> {
+    let Some(uptime) = uptime else {
+        return render_svg(Badge::Uptime.label(), "unknown", GREY);
+    };
+    let color = match uptime * 100.0 {
+        percent if percent >= 99.9 => GREEN,
+        percent if percent >= 99.0 => YELLOW_GREEN,
+        percent if percent >= 95.0 => YELLOW,
+        _ => RED,
+    };
+    render_svg(
+        Badge::Uptime.label(),
+        &format!("{:.2}%", uptime * 100.0),
+        color,
+    )
+}
+
+/// The average response time of a website, `None` if there were no responses.
+fn response_time(average_ms: Option<i64>) -> Result<String> {
+    let Some(average_ms) = average_ms else {
+        return render_svg(Badge::ResponseTime.label(), "unknown", GREY);
+    };
+    let color = match average_ms {
+        0..=199 => GREEN,
+        200..=499 => YELLOW_GREEN,
+        500..=999 => YELLOW,
+        _ => RED,
+    };
+    render_svg(
+        Badge::ResponseTime.label(),
+        &format!("{average_ms} ms"),
+        color,
+    )
+}
@@ -1,5 +1,5 @@
 use chrono::Utc;
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, time::Instant};
 use tracing::info;
 
 use crate::config::WebsiteConfig;
@@ -10,26 +10,44 @@ pub struct Client {
 }
 
 pub struct Results {
+    /// The result of every website, by slug.
     pub states: BTreeMap<String, CheckResult>,
 }
 
 pub struct CheckResult {
     pub time: chrono::DateTime<Utc>,
     pub state: CheckState,
+    /// How long it took to get a response, if there was one.
+    pub latency_ms: Option<i64>,
+    /// Why the check failed, not stored in the database.
+    pub reason: Option<String>,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy, sqlx::Type)]
-#[sqlx(rename_all = "snake_case")]
+#[derive(Debug, PartialEq, Clone, Copy, sqlx::Type, serde::Serialize, serde::Deserialize)]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
 pub enum CheckState {
     Ok,
     NotOk,
+    /// The website was paused, which is neither up nor down.
+    Paused,
 }
 
+/// Checks all websites, except for paused ones which are recorded as paused without a request.
 pub async fn do_checks(client: &Client) -> Results {
     let mut states = BTreeMap::new();
     for website in &client.websites {
-        let check_result = make_request(&client.req, website).await;
-        states.insert(website.name.clone(), check_result);
+        let check_result = if website.paused {
+            CheckResult {
+                time: Utc::now(),
+                state: CheckState::Paused,
+                latency_ms: None,
+                reason: None,
+            }
+        } else {
+            make_request(&client.req, website).await
+        };
+        states.insert(website.slug().to_owned(), check_result);
     }
 
     Results { states }
@@ -38,22 +56,46 @@ pub async fn do_checks(client: &Client) -> Results {
 #[tracing::instrument(skip(client))]
 async fn make_request(client: &reqwest::Client, website: &WebsiteConfig) -> CheckResult {
     let time = Utc::now();
+    let start = Instant::now();
     let result = client.get(website.url.clone()).send().await;
+    let latency_ms = start.elapsed().as_millis() as i64;
 
-    info!(?result, %website.url, "Made health request");
+    info!(?result, %website.url, latency_ms, "Made health request");
 
     match result {
+        Ok(res) if res.status().is_success() => CheckResult {
+            time,
+            state: CheckState::Ok,
+            latency_ms: Some(latency_ms),
+            reason: None,
+        },
         Ok(res) => CheckResult {
             time,
-            state: if res.status().is_success() {
-                CheckState::Ok
-            } else {
-                CheckState::NotOk
-            },
+            state: CheckState::NotOk,
+            latency_ms: Some(latency_ms),
+            reason: Some(format!("status {}", res.status())),
         },
-        Err(_) => CheckResult {
+        Err(err) => CheckResult {
             time,
             state: CheckState::NotOk,
+            latency_ms: None,
+            reason: Some(error_chain(&err)),
         },
     }
 }
+
+/// Joins the error with its sources, since reqwest only says that sending the request failed.
+/// Some errors already include their source in the message, which is not repeated.
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut chain = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        let message = err.to_string();
+        if !chain.contains(&message) {
+            chain.push_str(": ");
+            chain.push_str(&message);
+        }
+        source = err.source();
+    }
+    chain
+}
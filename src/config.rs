@@ -1,22 +1,323 @@
-use eyre::{Context, Result};
-use url::Url;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+};
 
-#[derive(Debug, serde::Deserialize)]
+use chrono::{DateTime, Utc};
+use eyre::{bail, eyre, Context, ContextCompat, Result};
+use url::{Host, Url};
+
+#[derive(Debug, Clone, serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
+    /// How often all websites are checked.
+    #[schemars(range(min = 1))]
     pub interval_seconds: u64,
     pub websites: Vec<WebsiteConfig>,
+    /// Where history is stored, an SQLite path, a `postgres://` URL or `memory:`.
     #[serde(default = "default_db_url")]
     pub db_url: String,
+    #[serde(default)]
+    pub retention: Option<RetentionConfig>,
+    /// Glob patterns of further config files that contribute `websites`, relative to this file.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Descriptions and the display order of the groups websites are in.
+    #[serde(default)]
+    pub groups: Vec<GroupConfig>,
+    #[serde(default)]
+    pub web: WebConfig,
+    /// Generate the status page as static files after every check.
+    #[serde(default)]
+    pub static_site: Option<StaticSiteConfig>,
+    /// The URL the status page is reachable at, used for links in feeds.
+    /// Links are relative to `web.base_path` if missing.
+    #[serde(default)]
+    #[schemars(with = "Option<String>", url)]
+    pub public_url: Option<Url>,
+    /// Messages shown in the feeds, for example about planned maintenance.
+    #[serde(default)]
+    pub announcements: Vec<AnnouncementConfig>,
+    /// Who can see private websites and use the write APIs. Everyone is anonymous without it.
+    #[serde(default)]
+    pub auth: AuthConfig,
+}
+
+/// The ways requests can be authenticated, each granting a set of scopes.
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Tokens for scripts, sent as `Authorization: Bearer <token>`.
+    #[serde(default)]
+    pub tokens: Vec<TokenConfig>,
+    /// Users for browsers, logging in with HTTP basic auth.
+    #[serde(default)]
+    pub users: Vec<UserConfig>,
+    /// Trust a header with the user name set by an authenticating reverse proxy.
+    #[serde(default)]
+    pub proxy: Option<ProxyAuthConfig>,
+}
+
+/// What an authenticated request is allowed to do.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// See websites with `public: false`.
+    Private,
+    /// Report and update incidents.
+    Incidents,
+    /// Add, change and delete websites through the API.
+    Websites,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TokenConfig {
+    /// Identifies the token in logs.
+    pub name: String,
+    pub token: String,
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+    pub name: String,
+    /// The Argon2 hash of the password, as printed by `uptime hash-password`.
+    pub password_hash: String,
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProxyAuthConfig {
+    /// The header containing the name of the user, like `X-Forwarded-User`.
+    pub header: String,
+    /// The addresses of the proxies that are allowed to set the header.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+    /// Whether to trust the header on connections over a Unix domain socket. Anyone who can
+    /// connect to the socket can then log in as any user.
+    #[serde(default)]
+    pub trust_unix_socket: bool,
+    /// The scopes of every user authenticated by the proxy.
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AnnouncementConfig {
+    pub title: String,
+    #[serde(default)]
+    pub message: Option<String>,
+    /// When the announcement was posted, an RFC 3339 timestamp.
+    #[schemars(with = "String")]
+    pub time: DateTime<Utc>,
+    /// The names of the websites the announcement is about, all websites if empty.
+    #[serde(default)]
+    pub websites: Vec<String>,
+}
+
+/// Where the static status page is generated.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct StaticSiteConfig {
+    /// The directory to serve, which is replaced by a symlink to the latest generated site.
+    pub output: PathBuf,
+}
+
+/// Where the web server listens and which path it is served under.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WebConfig {
+    /// `host:port`, with IPv6 hosts in brackets like `[::]:3000`, or `unix:/path/to/socket`.
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    /// The path prefix everything is served under, for example `/status/` behind a reverse proxy.
+    #[serde(default = "default_base_path")]
+    pub base_path: String,
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            listen: default_listen(),
+            base_path: default_base_path(),
+        }
+    }
+}
+
+fn default_listen() -> String {
+    "0.0.0.0:3000".into()
+}
+
+fn default_base_path() -> String {
+    "/".into()
+}
+
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl WebConfig {
+    pub fn listen_addr(&self) -> Result<ListenAddr> {
+        match self.listen.strip_prefix("unix:") {
+            Some("") => bail!("listen address `{}` is missing a socket path", self.listen),
+            Some(path) => Ok(ListenAddr::Unix(PathBuf::from(path))),
+            None => self.listen.parse().map(ListenAddr::Tcp).wrap_err_with(|| {
+                format!(
+                    "invalid listen address `{}`, expected `host:port` or `unix:/path`",
+                    self.listen
+                )
+            }),
+        }
+    }
+
+    /// The base path with a leading and without a trailing slash, empty for the root.
+    pub fn base_path(&self) -> String {
+        let base_path = self.base_path.trim_matches('/');
+        if base_path.is_empty() {
+            String::new()
+        } else {
+            format!("/{base_path}")
+        }
+    }
+}
+
+/// A section of websites on the status page.
+#[derive(Debug, Clone, serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GroupConfig {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Groups are shown in ascending order, then by name. Defaults to 0.
+    #[serde(default)]
+    pub order: i64,
+}
+
+/// A config file included by [`Config::include`].
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IncludedConfig {
+    #[serde(default)]
+    websites: Vec<WebsiteConfig>,
 }
 
 fn default_db_url() -> String {
     "uptime.db".into()
 }
 
-#[derive(serde::Deserialize)]
+/// How long check history is kept at which precision.
+///
+/// Raw series older than `raw_days` are rolled up into hourly aggregates, hourly aggregates older
+/// than `hourly_days` are rolled up into daily aggregates and everything older than
+/// `delete_after_days` is deleted.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionConfig {
+    #[serde(default = "default_raw_days")]
+    pub raw_days: u64,
+    #[serde(default = "default_hourly_days")]
+    pub hourly_days: u64,
+    #[serde(default)]
+    pub delete_after_days: Option<u64>,
+    #[serde(default = "default_retention_interval_seconds")]
+    #[schemars(range(min = 1))]
+    pub interval_seconds: u64,
+}
+
+fn default_raw_days() -> u64 {
+    90
+}
+
+fn default_hourly_days() -> u64 {
+    365
+}
+
+fn default_retention_interval_seconds() -> u64 {
+    60 * 60
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct WebsiteConfig {
+    /// The name shown on the status page, must be unique.
     pub name: String,
+    /// The URL that is requested, which is OK if it responds with a success status.
+    #[schemars(with = "String", url)]
     pub url: Url,
+    /// The stable identifier of the website that its history is stored under.
+    /// Defaults to the slug of the website previously known under this name or one of the
+    /// `previous_names`, or the name itself for new websites.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slug: Option<String>,
+    /// Names this website was configured with before, so its history is kept after a rename.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_names: Vec<String>,
+    /// Archived websites are no longer checked, but their history is still shown.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+    /// Paused websites are not checked until they are resumed, for example during maintenance.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub paused: bool,
+    /// The group this website is shown in on the status page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Free-form tags, which the status page and API can be filtered by.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Private websites are only shown to users with the `private` scope.
+    #[serde(default = "default_public", skip_serializing_if = "is_public")]
+    pub public: bool,
+}
+
+fn default_public() -> bool {
+    true
+}
+
+fn is_public(public: &bool) -> bool {
+    *public
+}
+
+impl WebsiteConfig {
+    /// The slug of the website. Only falls back to the name before the websites were synced with
+    /// the database.
+    pub fn slug(&self) -> &str {
+        self.slug.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Whether the website is checked every tick.
+    pub fn is_checked(&self) -> bool {
+        !self.archived && !self.paused
+    }
+
+    /// Problems with the URL that make the website impossible to check, prefixed with `url: `.
+    pub(crate) fn url_problems(&self) -> Vec<String> {
+        let url = &self.url;
+        let mut problems = Vec::new();
+        if !matches!(url.scheme(), "http" | "https") {
+            problems.push(format!(
+                "url: unsupported scheme `{}` in `{url}`, only http and https can be checked",
+                url.scheme()
+            ));
+        }
+        match url.host() {
+            None => problems.push(format!("url: `{url}` has no host")),
+            Some(Host::Ipv4(ip)) if ip.is_unspecified() => {
+                problems.push(format!("url: `{url}` points to the unspecified address"))
+            }
+            Some(Host::Ipv6(ip)) if ip.is_unspecified() => {
+                problems.push(format!("url: `{url}` points to the unspecified address"))
+            }
+            _ => {}
+        }
+        problems
+    }
 }
 
 impl std::fmt::Debug for WebsiteConfig {
@@ -24,13 +325,662 @@ impl std::fmt::Debug for WebsiteConfig {
         f.debug_struct("WebsiteConfig")
             .field("name", &self.name)
             .field("url", &format_args!("{}", self.url))
+            .field("slug", &self.slug)
             .finish()
     }
 }
 
+impl Config {
+    /// Checks everything that can't be expressed in the types, reporting all problems at once.
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+
+        if self.interval_seconds == 0 {
+            errors.push("interval_seconds: must be greater than zero".to_owned());
+        }
+        if let Some(retention) = &self.retention {
+            if retention.interval_seconds == 0 {
+                errors.push("retention.interval_seconds: must be greater than zero".to_owned());
+            }
+            if retention.hourly_days < retention.raw_days {
+                errors.push(format!(
+                    "retention.hourly_days: must be at least raw_days ({})",
+                    retention.raw_days
+                ));
+            }
+            if let Some(delete_after_days) = retention.delete_after_days {
+                if delete_after_days < retention.hourly_days {
+                    errors.push(format!(
+                        "retention.delete_after_days: must be at least hourly_days ({})",
+                        retention.hourly_days
+                    ));
+                }
+            }
+        }
+
+        if let Err(err) = self.web.listen_addr() {
+            errors.push(format!("web.listen: {err}"));
+        }
+        if !self.web.base_path.starts_with('/') {
+            errors.push("web.base_path: must start with `/`".to_owned());
+        }
+
+        let mut groups = HashMap::new();
+        for (i, group) in self.groups.iter().enumerate() {
+            if let Some(first) = groups.insert(&group.name, i) {
+                errors.push(format!(
+                    "groups[{i}] ({}): name is already used by groups[{first}]",
+                    group.name
+                ));
+            }
+        }
+
+        let mut names = HashMap::new();
+        let mut slugs = HashMap::new();
+        for (i, website) in self.websites.iter().enumerate() {
+            let at = format!("websites[{i}] ({})", website.name);
+
+            if website.name.trim().is_empty() {
+                errors.push(format!("websites[{i}].name: must not be empty"));
+            }
+            if let Some(first) = names.insert(&website.name, i) {
+                errors.push(format!("{at}: name is already used by websites[{first}]"));
+            }
+            if let Some(slug) = &website.slug {
+                if let Some(first) = slugs.insert(slug, i) {
+                    errors.push(format!(
+                        "{at}: slug `{slug}` is already used by websites[{first}]"
+                    ));
+                }
+            }
+
+            for problem in website.url_problems() {
+                errors.push(format!("{at}.{problem}"));
+            }
+        }
+
+        let mut token_names = HashMap::new();
+        for (i, token) in self.auth.tokens.iter().enumerate() {
+            if let Some(first) = token_names.insert(&token.name, i) {
+                errors.push(format!(
+                    "auth.tokens[{i}] ({}): name is already used by auth.tokens[{first}]",
+                    token.name
+                ));
+            }
+            if token.token.len() < 16 {
+                errors.push(format!(
+                    "auth.tokens[{i}] ({}): token must be at least 16 characters long",
+                    token.name
+                ));
+            }
+        }
+        let mut user_names = HashMap::new();
+        for (i, user) in self.auth.users.iter().enumerate() {
+            if let Some(first) = user_names.insert(&user.name, i) {
+                errors.push(format!(
+                    "auth.users[{i}] ({}): name is already used by auth.users[{first}]",
+                    user.name
+                ));
+            }
+            if let Err(err) = argon2::PasswordHash::new(&user.password_hash) {
+                errors.push(format!(
+                    "auth.users[{i}] ({}).password_hash: {err}, create one with `uptime hash-password`",
+                    user.name
+                ));
+            }
+        }
+
+        for (i, announcement) in self.announcements.iter().enumerate() {
+            for name in &announcement.websites {
+                if !names.contains_key(name) {
+                    errors.push(format!(
+                        "announcements[{i}] ({}): unknown website `{name}`",
+                        announcement.title
+                    ));
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            bail!("invalid config:\n  {}", errors.join("\n  "));
+        }
+        Ok(())
+    }
+
+    /// The history shown if no range is given: the part that retention keeps at full precision,
+    /// `raw_days` even if no retention is configured.
+    pub fn default_range(&self) -> chrono::Duration {
+        let raw_days = self
+            .retention
+            .as_ref()
+            .map_or_else(default_raw_days, |retention| retention.raw_days);
+        raw_days
+            .checked_mul(24 * 60 * 60)
+            .and_then(|secs| chrono::Duration::from_std(std::time::Duration::from_secs(secs)).ok())
+            .unwrap_or_else(chrono::Duration::max_value)
+    }
+}
+
+/// The JSON Schema of the config, for completion in editors.
+pub fn json_schema() -> String {
+    let mut schema = schemars::schema_for!(Config);
+
+    let objects =
+        std::iter::once(&mut schema.schema).chain(schema.definitions.values_mut().filter_map(
+            |schema| match schema {
+                schemars::schema::Schema::Object(object) => Some(object),
+                schemars::schema::Schema::Bool(_) => None,
+            },
+        ));
+    for object in objects {
+        let properties = &mut object.object().properties;
+        for field in SECRET_FIELDS {
+            if properties.contains_key(*field) {
+                let file_field = schemars::schema::SchemaObject {
+                    instance_type: Some(schemars::schema::InstanceType::String.into()),
+                    metadata: Some(Box::new(schemars::schema::Metadata {
+                        description: Some(format!("A file to read `{field}` from.")),
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                };
+                properties.insert(format!("{field}_file"), file_field.into());
+            }
+        }
+    }
+
+    serde_json::to_string_pretty(&schema).expect("schema is valid JSON")
+}
+
+/// Reads the config, as JSON, TOML or YAML depending on the file extension, and merges the
+/// websites of all included files into it.
+///
+/// `${VAR}` in strings is replaced with the environment variable `VAR` and `$$` with `$`. Secret
+/// fields can be read from a file with their `_file` variant, see [`SECRET_FIELDS`].
 pub fn read_config(config_path: &str) -> Result<Config> {
-    let config_str = std::fs::read_to_string(config_path)
-        .wrap_err_with(|| format!("opening config at '{config_path}'"))?;
+    let mut config: Config = read_config_file(Path::new(config_path))?;
+
+    let mut sources = HashMap::new();
+    for website in &config.websites {
+        sources.insert(website.name.clone(), PathBuf::from(config_path));
+    }
+
+    for path in include_paths(config_path, &config.include)? {
+        let included: IncludedConfig = read_config_file(&path)?;
+        for website in included.websites {
+            if let Some(source) = sources.insert(website.name.clone(), path.clone()) {
+                bail!(
+                    "website {} in '{}' is already defined in '{}'",
+                    website.name,
+                    path.display(),
+                    source.display()
+                );
+            }
+            config.websites.push(website);
+        }
+    }
+
+    config.validate()?;
+
+    Ok(config)
+}
+
+/// All files matching the `include` patterns of the config at `config_path`, in a stable order.
+pub fn include_paths(config_path: &str, include: &[String]) -> Result<Vec<PathBuf>> {
+    let base = Path::new(config_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+
+    let mut paths = Vec::new();
+    for pattern in include {
+        let pattern = base.join(pattern);
+        let pattern = pattern.to_str().wrap_err("include pattern is not UTF-8")?;
+        for path in glob::glob(pattern).wrap_err_with(|| format!("invalid include `{pattern}`"))? {
+            paths.push(path.wrap_err_with(|| format!("reading include `{pattern}`"))?);
+        }
+    }
+
+    Ok(paths)
+}
+
+fn read_config_file<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let config_str = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("opening config at '{}'", path.display()))?;
+
+    let extension = path.extension().and_then(|extension| extension.to_str());
+
+    let mut value: serde_json::Value = match extension {
+        Some("toml") => toml::from_str(&config_str)
+            .map(toml_to_json)
+            .map_err(eyre::Report::new),
+        Some("yaml" | "yml") => serde_yaml::from_str(&config_str).map_err(eyre::Report::new),
+        Some("json") | None => serde_json::from_str(&config_str).map_err(eyre::Report::new),
+        Some(other) => bail!(
+            "unknown config format `.{other}` of '{}', expected .json, .toml or .yaml",
+            path.display()
+        ),
+    }
+    .wrap_err_with(|| format!("reading config file '{}'", path.display()))?;
+
+    resolve_value(&mut value, "")
+        .wrap_err_with(|| format!("reading config file '{}'", path.display()))?;
+
+    // Report the path to errors, a message alone is hard to find in a long config.
+    serde_path_to_error::deserialize(value)
+        .map_err(|err| eyre!("{}: {}", err.path(), err.inner()))
+        .wrap_err_with(|| format!("reading config file '{}'", path.display()))
+}
+
+/// Converts TOML into JSON, with datetimes as RFC 3339 strings like in the other formats instead of
+/// the map toml uses internally.
+fn toml_to_json(value: toml::Value) -> serde_json::Value {
+    match value {
+        toml::Value::String(string) => string.into(),
+        toml::Value::Integer(integer) => integer.into(),
+        toml::Value::Float(float) => float.into(),
+        toml::Value::Boolean(boolean) => boolean.into(),
+        toml::Value::Datetime(datetime) => datetime.to_string().into(),
+        toml::Value::Array(values) => values.into_iter().map(toml_to_json).collect(),
+        toml::Value::Table(fields) => fields
+            .into_iter()
+            .map(|(key, value)| (key, toml_to_json(value)))
+            .collect(),
+    }
+}
+
+/// Fields containing secrets, which can also be read from the file at `<field>_file` instead,
+/// for example from Docker or Kubernetes secrets. The lookup goes by field name at any depth, so a
+/// new secret field only has to be added here, like request headers or notification credentials
+/// once the config has them.
+pub const SECRET_FIELDS: &[&str] = &["db_url", "token", "password_hash"];
+
+/// Interpolates environment variables and reads secret files in all strings of `value`.
+fn resolve_value(value: &mut serde_json::Value, path: &str) -> Result<()> {
+    let join = |key: &str| match path {
+        "" => key.to_owned(),
+        path => format!("{path}.{key}"),
+    };
+
+    match value {
+        serde_json::Value::String(string) => {
+            *string = interpolate_env(string).wrap_err_with(|| path.to_owned())?;
+        }
+        serde_json::Value::Array(values) => {
+            for (i, value) in values.iter_mut().enumerate() {
+                resolve_value(value, &format!("{path}[{i}]"))?;
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            let mut secrets = Vec::new();
+            for field in SECRET_FIELDS {
+                let file_field = format!("{field}_file");
+                let Some(file) = fields.remove(&file_field) else {
+                    continue;
+                };
+                let at = join(&file_field);
+
+                if fields.contains_key(*field) {
+                    bail!("{at}: `{field}` and `{file_field}` can't both be set");
+                }
+                let serde_json::Value::String(file) = file else {
+                    bail!("{at}: must be a path");
+                };
+                let file = interpolate_env(&file).wrap_err_with(|| at.clone())?;
+                let secret = std::fs::read_to_string(&file)
+                    .wrap_err_with(|| format!("{at}: reading secret from '{file}'"))?;
+
+                secrets.push((
+                    field.to_string(),
+                    secret.trim_end_matches(['\n', '\r']).to_owned(),
+                ));
+            }
+
+            for (key, value) in fields.iter_mut() {
+                resolve_value(value, &join(key))?;
+            }
+
+            // Secrets are inserted last, they are used verbatim.
+            for (field, secret) in secrets {
+                fields.insert(field, serde_json::Value::String(secret));
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Replaces `${VAR}` with the environment variable `VAR` and `$$` with `$`.
+fn interpolate_env(string: &str) -> Result<String> {
+    let mut result = String::with_capacity(string.len());
+    let mut rest = string;
+
+    while let Some(dollar) = rest.find('$') {
+        result.push_str(&rest[..dollar]);
+        rest = &rest[dollar..];
+
+        if let Some(after) = rest.strip_prefix("$$") {
+            result.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let Some(end) = after.find('}') else {
+                bail!("unclosed `${{` in `{string}`");
+            };
+            let name = &after[..end];
+            let value = std::env::var(name)
+                .wrap_err_with(|| format!("environment variable `{name}` is not set"))?;
+            result.push_str(&value);
+            rest = &after[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
 
-    serde_json::from_str(&config_str).wrap_err("reading config file")
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::read_config;
+
+    /// The directory for the config files of a test. `name` must be unique across all tests.
+    fn dir_of(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("uptime-config-test-{name}-{}", std::process::id()))
+    }
+
+    /// A fresh directory for the config files of a test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = dir_of(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes `files` into a fresh directory and reads the config from the first one.
+    fn read(name: &str, files: &[(&str, &str)]) -> eyre::Result<super::Config> {
+        let dir = test_dir(name);
+        for (path, contents) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        let config = read_config(dir.join(files[0].0).to_str().unwrap());
+        std::fs::remove_dir_all(dir).unwrap();
+        config
+    }
+
+    #[test]
+    fn formats_are_equivalent() {
+        let json = r#"{
+            "interval_seconds": 30,
+            "websites": [
+                { "name": "api", "url": "https://api.example.com", "group": "Infra", "tags": ["a"] }
+            ],
+            "retention": { "raw_days": 7 },
+            "announcements": [{ "title": "Maintenance", "time": "2024-05-01T20:00:00Z" }]
+        }"#;
+        let toml = r#"
+            interval_seconds = 30
+            retention = { raw_days = 7 }
+
+            [[websites]]
+            name = "api"
+            url = "https://api.example.com"
+            group = "Infra"
+            tags = ["a"]
+
+            [[announcements]]
+            title = "Maintenance"
+            time = 2024-05-01T20:00:00Z
+        "#;
+        let yaml = r#"
+            interval_seconds: 30
+            websites:
+              - name: api
+                url: https://api.example.com
+                group: Infra
+                tags: [a]
+            retention:
+              raw_days: 7
+            announcements:
+              - title: Maintenance
+                time: 2024-05-01T20:00:00Z
+        "#;
+
+        let json = read("formats_json", &[("uptime.json", json)]).unwrap();
+        let toml = read("formats_toml", &[("uptime.toml", toml)]).unwrap();
+        let yaml = read("formats_yaml", &[("uptime.yaml", yaml)]).unwrap();
+
+        assert_eq!(
+            json.announcements[0].time.to_rfc3339(),
+            "2024-05-01T20:00:00+00:00"
+        );
+        for other in [&toml, &yaml] {
+            assert_eq!(format!("{json:?}"), format!("{other:?}"));
+            assert_eq!(json.announcements, other.announcements);
+            assert_eq!(json.retention, other.retention);
+            assert_eq!(json.websites[0].group, other.websites[0].group);
+            assert_eq!(json.websites[0].tags, other.websites[0].tags);
+        }
+    }
+
+    /// The problems reported for an invalid config, one per line.
+    fn problems(err: eyre::Report) -> Vec<String> {
+        let message = err.to_string();
+        let problems = message
+            .strip_prefix("invalid config:\n")
+            .unwrap_or_else(|| panic!("not a validation error: {err:?}"));
+        problems
+            .lines()
+            .map(|line| line.trim().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn validate_reports_all_problems() {
+        let json = r#"{
+            "interval_seconds": 0,
+            "websites": [
+                { "name": "a", "url": "https://a.example.com", "slug": "x" },
+                { "name": "a", "url": "ftp://b.example.com" },
+                { "name": " ", "url": "https://c.example.com", "slug": "x" }
+            ],
+            "retention": { "raw_days": 30, "hourly_days": 7, "delete_after_days": 1 },
+            "groups": [{ "name": "Infra" }, { "name": "Infra" }],
+            "web": { "listen": "localhost", "base_path": "status" },
+            "announcements": [{ "title": "Oops", "time": "2024-05-01T20:00:00Z", "websites": ["b"] }],
+            "auth": {
+                "tokens": [{ "name": "ci", "token": "short", "scopes": [] }],
+                "users": [{ "name": "admin", "password_hash": "hunter2", "scopes": [] }]
+            }
+        }"#;
+
+        let err = read("validate", &[("uptime.json", json)]).unwrap_err();
+        assert_eq!(
+            problems(err),
+            [
+                "interval_seconds: must be greater than zero",
+                "retention.hourly_days: must be at least raw_days (30)",
+                "retention.delete_after_days: must be at least hourly_days (7)",
+                "web.listen: invalid listen address `localhost`, expected `host:port` or `unix:/path`",
+                "web.base_path: must start with `/`",
+                "groups[1] (Infra): name is already used by groups[0]",
+                "websites[1] (a): name is already used by websites[0]",
+                "websites[1] (a).url: unsupported scheme `ftp` in `ftp://b.example.com/`, only http and https can be checked",
+                "websites[2].name: must not be empty",
+                "websites[2] ( ): slug `x` is already used by websites[0]",
+                "auth.tokens[0] (ci): token must be at least 16 characters long",
+                "auth.users[0] (admin).password_hash: password hash string missing field, create one with `uptime hash-password`",
+                "announcements[0] (Oops): unknown website `b`",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let json = r#"{
+            "interval_seconds": 30,
+            "websites": [{ "name": "a", "url": "https://a.example.com", "tgas": ["typo"] }]
+        }"#;
+
+        let err = read("unknown_fields", &[("uptime.json", json)]).unwrap_err();
+        let cause = err.root_cause().to_string();
+        assert!(
+            cause.starts_with("websites[0].tgas: unknown field `tgas`"),
+            "{cause}"
+        );
+    }
+
+    #[test]
+    fn interpolate_env_replaces_variables() {
+        std::env::set_var("UPTIME_TEST_INTERPOLATE", "example.com");
+
+        let interpolate = |string| super::interpolate_env(string).unwrap();
+        assert_eq!(
+            interpolate("https://${UPTIME_TEST_INTERPOLATE}/health"),
+            "https://example.com/health"
+        );
+        assert_eq!(
+            interpolate("$${UPTIME_TEST_INTERPOLATE}"),
+            "${UPTIME_TEST_INTERPOLATE}"
+        );
+        assert_eq!(interpolate("costs $5, $$ and $"), "costs $5, $ and $");
+
+        let err = super::interpolate_env("${UPTIME_TEST_UNSET}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "environment variable `UPTIME_TEST_UNSET` is not set"
+        );
+        let err = super::interpolate_env("${UPTIME_TEST_INTERPOLATE").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unclosed `${` in `${UPTIME_TEST_INTERPOLATE`"
+        );
+    }
+
+    #[test]
+    fn resolve_value_reports_the_path_of_unset_variables() {
+        let mut value = serde_json::json!({ "websites": [{ "url": "${UPTIME_TEST_UNSET}" }] });
+        let err = super::resolve_value(&mut value, "").unwrap_err();
+        assert_eq!(
+            err.chain().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "websites[0].url",
+                "environment variable `UPTIME_TEST_UNSET` is not set",
+                "environment variable not found"
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_value_reads_secret_files() {
+        let dir = test_dir("secret_files");
+        std::env::set_var("UPTIME_TEST_SECRETS", dir.to_str().unwrap());
+        std::fs::write(dir.join("db_url"), "postgres://localhost/uptime\n").unwrap();
+        std::fs::write(dir.join("token"), "${NOT_INTERPOLATED}\r\n").unwrap();
+        std::fs::write(dir.join("hash"), "$argon2id$hash").unwrap();
+
+        let mut value = serde_json::json!({
+            "db_url_file": "${UPTIME_TEST_SECRETS}/db_url",
+            "auth": {
+                "tokens": [{ "name": "ci", "token_file": "${UPTIME_TEST_SECRETS}/token" }],
+                "users": [{ "name": "admin", "password_hash_file": "${UPTIME_TEST_SECRETS}/hash" }]
+            }
+        });
+        super::resolve_value(&mut value, "").unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "db_url": "postgres://localhost/uptime",
+                "auth": {
+                    "tokens": [{ "name": "ci", "token": "${NOT_INTERPOLATED}" }],
+                    "users": [{ "name": "admin", "password_hash": "$argon2id$hash" }]
+                }
+            })
+        );
+
+        let mut both = serde_json::json!({
+            "auth": { "tokens": [{ "token": "secret", "token_file": "${UPTIME_TEST_SECRETS}/token" }] }
+        });
+        let err = super::resolve_value(&mut both, "").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "auth.tokens[0].token_file: `token` and `token_file` can't both be set"
+        );
+
+        let mut missing = serde_json::json!({ "db_url_file": "${UPTIME_TEST_SECRETS}/missing" });
+        let err = super::resolve_value(&mut missing, "").unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("db_url_file: reading secret from"),
+            "{err}"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn includes_add_websites() {
+        let main = r#"{
+            "interval_seconds": 30,
+            "include": ["sites/*"],
+            "websites": [{ "name": "main", "url": "https://main.example.com" }]
+        }"#;
+        let b = r#"{ "websites": [{ "name": "b", "url": "https://b.example.com" }] }"#;
+        let a = r#"
+            [[websites]]
+            name = "a"
+            url = "https://a.example.com"
+        "#;
+
+        let config = read(
+            "includes",
+            &[
+                ("uptime.json", main),
+                ("sites/b.json", b),
+                ("sites/a.toml", a),
+            ],
+        )
+        .unwrap();
+        let names = config
+            .websites
+            .iter()
+            .map(|website| website.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["main", "a", "b"]);
+    }
+
+    #[test]
+    fn includes_must_not_redefine_websites() {
+        let main = r#"{
+            "interval_seconds": 30,
+            "include": ["sites/*.json"],
+            "websites": [{ "name": "main", "url": "https://main.example.com" }]
+        }"#;
+        let a = r#"{ "websites": [{ "name": "a", "url": "https://a.example.com" }] }"#;
+        let b = r#"{ "websites": [{ "name": "main", "url": "https://other.example.com" }] }"#;
+
+        let dir = dir_of("duplicate_include");
+        let err = read(
+            "duplicate_include",
+            &[
+                ("uptime.json", main),
+                ("sites/a.json", a),
+                ("sites/b.json", b),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "website main in '{}' is already defined in '{}'",
+                dir.join("sites/b.json").display(),
+                dir.join("uptime.json").display()
+            )
+        );
+    }
 }
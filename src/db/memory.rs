@@ -0,0 +1,620 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+use eyre::Result;
+use futures_util::{stream::BoxStream, StreamExt};
+
+use super::{
+    history_bounds, insert_single_result_series_in_memory, resolve_slugs, Bin, CheckAggregate,
+    CheckSeries, Incident, IncidentUpdate, NewIncident, Resolution, SaveWebsite, StateMillis,
+    Storage, StoredWebsite, Website,
+};
+use crate::{
+    client::{CheckState, Results},
+    config::WebsiteConfig,
+};
+
+/// Keeps everything in memory, mostly useful for tests and trying things out.
+#[derive(Default)]
+pub struct MemoryStorage {
+    tables: Mutex<Tables>,
+}
+
+#[derive(Default)]
+struct Tables {
+    series: Vec<CheckSeries>,
+    /// The index of the latest series of every website.
+    latest_cache: HashMap<String, usize>,
+    aggregates: BTreeMap<(String, Resolution, DateTime<Utc>), CheckAggregate>,
+    /// All websites by slug, with whether they are configured.
+    websites: BTreeMap<String, (Website, bool)>,
+    /// The websites managed through the API by slug.
+    managed: BTreeMap<String, WebsiteConfig>,
+    /// All incidents, their id is their index plus one.
+    incidents: Vec<Incident>,
+}
+
+impl Tables {
+    fn website_series<'a>(&'a self, website: &'a str) -> impl Iterator<Item = &'a CheckSeries> {
+        self.series
+            .iter()
+            .filter(move |series| series.website == website)
+    }
+
+    /// All series of a website overlapping with `range`, ordered by time.
+    fn series_in_range(&self, website: &str, range: Range<DateTime<Utc>>) -> Vec<CheckSeries> {
+        let mut series = self
+            .website_series(website)
+            .filter(|series| {
+                series.request_time_range_end >= range.start
+                    && series.request_time_range_start <= range.end
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        series.sort_by_key(|series| series.request_time_range_start);
+        series
+    }
+
+    fn website_aggregates<'a>(
+        &'a self,
+        website: &'a str,
+    ) -> impl Iterator<Item = &'a CheckAggregate> {
+        self.aggregates
+            .values()
+            .filter(move |aggregate| aggregate.website == website)
+    }
+
+    fn upsert_aggregates(&mut self, aggregates: Vec<CheckAggregate>) {
+        for aggregate in aggregates {
+            let key = (
+                aggregate.website.clone(),
+                aggregate.resolution,
+                aggregate.bucket_start,
+            );
+            match self.aggregates.get_mut(&key) {
+                Some(existing) => existing.merge(&aggregate),
+                None => {
+                    self.aggregates.insert(key, aggregate);
+                }
+            }
+        }
+    }
+
+    /// Adds a website managed through the API, or replaces one with `replace`, like the unique
+    /// slugs and names of the SQL backends.
+    fn save_managed_website(&mut self, website: &WebsiteConfig, replace: bool) -> SaveWebsite {
+        let slug = website.slug().to_owned();
+        match self.websites.get(&slug) {
+            Some((_, true)) => return SaveWebsite::SlugTaken,
+            Some((existing, false)) if existing.managed && !replace => {
+                return SaveWebsite::SlugTaken
+            }
+            _ => {}
+        }
+        let name_taken = self.websites.values().any(|(other, configured)| {
+            other.slug != slug && other.name == website.name && (*configured || other.managed)
+        });
+        if name_taken {
+            return SaveWebsite::NameTaken;
+        }
+
+        self.websites.insert(
+            slug.clone(),
+            (
+                Website {
+                    slug: slug.clone(),
+                    name: website.name.clone(),
+                    archived: website.archived,
+                    group: website.group.clone(),
+                    tags: sqlx::types::Json(website.tags.clone()),
+                    public: website.public,
+                    managed: true,
+                    paused: website.paused,
+                },
+                false,
+            ),
+        );
+        let mut website = website.clone();
+        website.slug = Some(slug.clone());
+        self.managed.insert(slug, website);
+        SaveWebsite::Saved
+    }
+
+    /// Removes all series matching `remove`, returning them.
+    fn remove_series(&mut self, remove: impl Fn(&CheckSeries) -> bool) -> Vec<CheckSeries> {
+        let (removed, kept) = std::mem::take(&mut self.series)
+            .into_iter()
+            .partition(|series| remove(series));
+        self.series = kept;
+        self.rebuild_latest_cache();
+
+        removed
+    }
+
+    /// Needs to be called after the indices of series have shifted.
+    fn rebuild_latest_cache(&mut self) {
+        self.latest_cache.clear();
+        for (idx, series) in self.series.iter().enumerate() {
+            let latest = self
+                .latest_cache
+                .entry(series.website.clone())
+                .or_insert(idx);
+            if self.series[*latest].request_time_range_end <= series.request_time_range_end {
+                *latest = idx;
+            }
+        }
+    }
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn tables(&self) -> std::sync::MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for MemoryStorage {
+    async fn migrate(&self, _interval_seconds: u64) -> Result<()> {
+        Ok(())
+    }
+
+    async fn sync_websites(&self, websites: &mut [WebsiteConfig]) -> Result<()> {
+        let mut tables = self.tables();
+
+        let stored = tables
+            .websites
+            .values()
+            .map(|(website, configured)| StoredWebsite {
+                slug: website.slug.clone(),
+                name: website.name.clone(),
+                configured: *configured,
+                managed: website.managed,
+            })
+            .collect();
+        resolve_slugs(websites, stored)?;
+
+        for (_, configured) in tables.websites.values_mut() {
+            *configured = false;
+        }
+        for website in websites.iter() {
+            let slug = website.slug().to_owned();
+            let paused = tables
+                .websites
+                .get(&slug)
+                .is_some_and(|(existing, _)| existing.paused);
+            tables.websites.insert(
+                slug.clone(),
+                (
+                    Website {
+                        slug,
+                        name: website.name.clone(),
+                        archived: website.archived,
+                        group: website.group.clone(),
+                        tags: sqlx::types::Json(website.tags.clone()),
+                        public: website.public,
+                        managed: false,
+                        paused,
+                    },
+                    true,
+                ),
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn insert_results_series(&self, interval_seconds: u64, results: &Results) -> Result<()> {
+        let mut tables = self.tables();
+        let Tables {
+            series,
+            latest_cache,
+            ..
+        } = &mut *tables;
+
+        for (website, check) in results.states.iter() {
+            insert_single_result_series_in_memory(
+                series,
+                latest_cache,
+                interval_seconds,
+                website,
+                check,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    async fn get_latest_series_for_website(&self, website: &str) -> Result<Option<CheckSeries>> {
+        let tables = self.tables();
+        Ok(tables
+            .latest_cache
+            .get(website)
+            .map(|idx| tables.series[*idx].clone()))
+    }
+
+    async fn merge_series(&self, series: &[CheckSeries]) -> Result<usize> {
+        let mut tables = self.tables();
+
+        let mut inserted = 0;
+        for series in series {
+            let exists = tables.website_series(&series.website).any(|existing| {
+                existing.request_time_range_start == series.request_time_range_start
+            });
+            if exists {
+                continue;
+            }
+
+            tables
+                .websites
+                .entry(series.website.clone())
+                .or_insert_with(|| {
+                    let website = Website {
+                        slug: series.website.clone(),
+                        name: series.website.clone(),
+                        archived: false,
+                        group: None,
+                        tags: sqlx::types::Json(Vec::new()),
+                        public: true,
+                        managed: false,
+                        paused: false,
+                    };
+                    (website, false)
+                });
+            tables.series.push(series.clone());
+            inserted += 1;
+        }
+        tables.rebuild_latest_cache();
+
+        Ok(inserted)
+    }
+
+    async fn get_websites(&self) -> Result<Vec<Website>> {
+        let mut websites = self
+            .tables()
+            .websites
+            .values()
+            .filter(|(website, configured)| *configured || website.managed)
+            .map(|(website, _)| website.clone())
+            .collect::<Vec<_>>();
+        websites.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(websites)
+    }
+
+    async fn get_managed_websites(&self) -> Result<Vec<WebsiteConfig>> {
+        let mut websites = self.tables().managed.values().cloned().collect::<Vec<_>>();
+        websites.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(websites)
+    }
+
+    async fn create_managed_website(&self, website: &WebsiteConfig) -> Result<SaveWebsite> {
+        Ok(self.tables().save_managed_website(website, false))
+    }
+
+    async fn upsert_managed_website(&self, website: &WebsiteConfig) -> Result<SaveWebsite> {
+        Ok(self.tables().save_managed_website(website, true))
+    }
+
+    async fn delete_managed_website(&self, slug: &str) -> Result<bool> {
+        let mut tables = self.tables();
+        if tables.managed.remove(slug).is_none() {
+            return Ok(false);
+        }
+        if let Some((website, _)) = tables.websites.get_mut(slug) {
+            website.managed = false;
+            website.paused = false;
+        }
+        Ok(true)
+    }
+
+    async fn set_paused(&self, slug: &str, paused: bool) -> Result<bool> {
+        let mut tables = self.tables();
+        match tables.websites.get_mut(slug) {
+            Some((website, configured)) if *configured || website.managed => {
+                website.paused = paused;
+            }
+            _ => return Ok(false),
+        }
+        if let Some(managed) = tables.managed.get_mut(slug) {
+            managed.paused = paused;
+        }
+        Ok(true)
+    }
+
+    async fn get_history_bounds(&self, website: &str) -> Result<Option<Range<DateTime<Utc>>>> {
+        let tables = self.tables();
+
+        let series_start = tables
+            .website_series(website)
+            .map(|series| series.request_time_range_start)
+            .min();
+        let series_end = tables
+            .website_series(website)
+            .map(|series| series.request_time_range_end)
+            .max();
+        let first_aggregate = tables
+            .website_aggregates(website)
+            .min_by_key(|aggregate| aggregate.bucket_start)
+            .cloned();
+        let last_aggregate = tables
+            .website_aggregates(website)
+            .max_by_key(|aggregate| aggregate.bucket_end())
+            .cloned();
+
+        Ok(history_bounds(
+            (series_start, series_end),
+            first_aggregate,
+            last_aggregate,
+        ))
+    }
+
+    async fn get_checks_series_in_range(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<CheckSeries>> {
+        Ok(self.tables().series_in_range(website, range))
+    }
+
+    fn stream_checks_series_in_range<'a>(
+        &'a self,
+        website: &'a str,
+        range: Range<DateTime<Utc>>,
+    ) -> BoxStream<'a, Result<CheckSeries>> {
+        let series = self.tables().series_in_range(website, range);
+        futures_util::stream::iter(series.into_iter().map(Ok)).boxed()
+    }
+
+    async fn get_checks_aggregates_in_range(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<CheckAggregate>> {
+        let tables = self.tables();
+        let mut aggregates = tables
+            .website_aggregates(website)
+            .filter(|aggregate| {
+                aggregate.bucket_end() >= range.start && aggregate.bucket_start <= range.end
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        aggregates.sort_by_key(|aggregate| aggregate.bucket_start);
+        Ok(aggregates)
+    }
+
+    async fn get_bins(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+        bins: usize,
+    ) -> Result<Vec<Bin>> {
+        let range_start_millis = range.start.timestamp_millis() as f64;
+        let bin_millis =
+            (range.end.timestamp_millis() - range.start.timestamp_millis()) as f64 / bins as f64;
+
+        let series = self
+            .get_checks_series_in_range(website, range.clone())
+            .await?;
+        let aggregates = self.get_checks_aggregates_in_range(website, range).await?;
+
+        let segments = series
+            .iter()
+            .map(|series| {
+                (
+                    series.request_time_range_start,
+                    series.request_time_range_end,
+                    i64::from(series.result == CheckState::Ok),
+                    i64::from(series.result == CheckState::NotOk),
+                    i64::from(series.result == CheckState::Paused),
+                )
+            })
+            .chain(aggregates.iter().map(|aggregate| {
+                (
+                    aggregate.bucket_start,
+                    aggregate.bucket_end(),
+                    aggregate.ok_series,
+                    aggregate.not_ok_series,
+                    aggregate.paused_series,
+                )
+            }))
+            .collect::<Vec<_>>();
+
+        Ok((0..bins)
+            .map(|idx| {
+                let bin_start = range_start_millis + idx as f64 * bin_millis;
+                let bin_end = range_start_millis + (idx + 1) as f64 * bin_millis;
+
+                let mut bin = Bin {
+                    idx: idx as i64,
+                    ok_series: 0,
+                    not_ok_series: 0,
+                    paused_series: 0,
+                };
+                for (start, end, ok_series, not_ok_series, paused_series) in &segments {
+                    if (start.timestamp_millis() as f64) <= bin_end
+                        && (end.timestamp_millis() as f64) >= bin_start
+                    {
+                        bin.ok_series += ok_series;
+                        bin.not_ok_series += not_ok_series;
+                        bin.paused_series += paused_series;
+                    }
+                }
+                bin
+            })
+            .collect())
+    }
+
+    async fn get_state_millis(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<StateMillis> {
+        let series = self
+            .get_checks_series_in_range(website, range.clone())
+            .await?;
+        let aggregates = self
+            .get_checks_aggregates_in_range(website, range.clone())
+            .await?;
+
+        Ok(StateMillis {
+            ok_millis: state_millis(&series, &aggregates, range.clone(), CheckState::Ok),
+            paused_millis: state_millis(&series, &aggregates, range, CheckState::Paused),
+        })
+    }
+
+    async fn get_last_ok(&self, website: &str) -> Result<Option<DateTime<Utc>>> {
+        let tables = self.tables();
+
+        let series = tables
+            .website_series(website)
+            .filter(|series| series.result == CheckState::Ok)
+            .map(|series| series.request_time_range_end)
+            .max();
+
+        Ok(series.or_else(|| {
+            tables
+                .website_aggregates(website)
+                .filter(|aggregate| aggregate.ok_series > 0)
+                .map(|aggregate| aggregate.bucket_end())
+                .max()
+        }))
+    }
+
+    async fn rollup_series(&self, cutoff: DateTime<Utc>) -> Result<usize> {
+        let mut tables = self.tables();
+
+        let series = tables.remove_series(|series| series.request_time_range_end < cutoff);
+        tables.upsert_aggregates(crate::retention::aggregate_series(&series));
+
+        Ok(series.len())
+    }
+
+    async fn rollup_aggregates(
+        &self,
+        from: Resolution,
+        to: Resolution,
+        cutoff: DateTime<Utc>,
+    ) -> Result<usize> {
+        let mut tables = self.tables();
+
+        let (old, kept) = std::mem::take(&mut tables.aggregates)
+            .into_iter()
+            .partition::<BTreeMap<_, _>, _>(|(_, aggregate)| {
+                aggregate.resolution == from && aggregate.bucket_start < cutoff
+            });
+        tables.aggregates = kept;
+
+        let old = old.into_values().collect::<Vec<_>>();
+        tables.upsert_aggregates(crate::retention::aggregate_aggregates(&old, to));
+
+        Ok(old.len())
+    }
+
+    async fn delete_before(&self, cutoff: DateTime<Utc>) -> Result<()> {
+        let mut tables = self.tables();
+
+        tables.remove_series(|series| series.request_time_range_end < cutoff);
+        tables
+            .aggregates
+            .retain(|_, aggregate| aggregate.bucket_start >= cutoff);
+
+        Ok(())
+    }
+
+    async fn create_incident(&self, incident: &NewIncident) -> Result<i64> {
+        let mut tables = self.tables();
+
+        let id = tables.incidents.len() as i64 + 1;
+        tables.incidents.push(Incident {
+            id,
+            title: incident.title.clone(),
+            severity: incident.severity,
+            websites: incident.websites.clone(),
+            created_at: incident.update.time,
+            updates: vec![incident.update.clone()],
+        });
+
+        Ok(id)
+    }
+
+    async fn add_incident_update(&self, incident: i64, update: &IncidentUpdate) -> Result<bool> {
+        let mut tables = self.tables();
+
+        match tables.incidents.iter_mut().find(|i| i.id == incident) {
+            Some(incident) => {
+                incident.updates.push(update.clone());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn get_incident(&self, incident: i64) -> Result<Option<Incident>> {
+        Ok(self
+            .tables()
+            .incidents
+            .iter()
+            .find(|i| i.id == incident)
+            .cloned())
+    }
+
+    async fn get_incidents(&self, since: DateTime<Utc>) -> Result<Vec<Incident>> {
+        let mut incidents = self
+            .tables()
+            .incidents
+            .iter()
+            .filter(|incident| !incident.is_resolved() || incident.latest_update().time > since)
+            .cloned()
+            .collect::<Vec<_>>();
+        incidents
+            .sort_by(|a, b| (b.latest_update().time, b.id).cmp(&(a.latest_update().time, a.id)));
+        Ok(incidents)
+    }
+}
+
+/// The time in milliseconds the series and aggregates were in `state` within `range`.
+/// Aggregates that only partly overlap with `range` count with the overlapping share of their
+/// bucket, as if their time was spread evenly over it.
+fn state_millis(
+    series: &[CheckSeries],
+    aggregates: &[CheckAggregate],
+    range: Range<DateTime<Utc>>,
+    state: CheckState,
+) -> i64 {
+    let overlap = |start, end| {
+        (std::cmp::min(end, range.end) - std::cmp::max(start, range.start))
+            .max(chrono::Duration::zero())
+    };
+
+    let series_duration = series
+        .iter()
+        .filter(|series| series.result == state)
+        .map(|series| {
+            overlap(
+                series.request_time_range_start,
+                series.request_time_range_end,
+            )
+        })
+        .fold(chrono::Duration::zero(), |sum, duration| sum + duration);
+
+    let aggregate_millis: i64 = aggregates
+        .iter()
+        .map(|aggregate| {
+            let bucket_millis = aggregate.resolution.seconds() * 1000;
+            let overlap_millis = overlap(aggregate.bucket_start, aggregate.bucket_end())
+                .num_milliseconds()
+                .min(bucket_millis);
+            (i128::from(aggregate.millis(state)) * i128::from(overlap_millis)
+                / i128::from(bucket_millis)) as i64
+        })
+        .sum();
+
+    series_duration.num_milliseconds() + aggregate_millis
+}
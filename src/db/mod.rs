@@ -0,0 +1,723 @@
+mod memory;
+mod postgres;
+mod sqlite;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, TimeZone, Utc};
+use eyre::{Context, ContextCompat, Result};
+use futures_util::stream::BoxStream;
+
+pub use memory::MemoryStorage;
+pub use postgres::PostgresStorage;
+pub use sqlite::SqliteStorage;
+
+use crate::{
+    client::{CheckResult, CheckState, Results},
+    config::WebsiteConfig,
+};
+
+/// Where the history of all checks is stored.
+#[async_trait::async_trait]
+pub trait Storage: Send + Sync {
+    /// Brings the schema up to date, migrating old data if necessary.
+    async fn migrate(&self, interval_seconds: u64) -> Result<()>;
+
+    /// Makes sure every configured website is stored, resolving renames via `previous_names`, and
+    /// fills in the slug of every website.
+    /// Websites that are not in the config anymore are marked as unconfigured.
+    /// Fails if a website conflicts with one managed through the API.
+    async fn sync_websites(&self, websites: &mut [WebsiteConfig]) -> Result<()>;
+
+    /// Inserts the results of a tick, extending the latest series of a website if the state
+    /// didn't change.
+    async fn insert_results_series(&self, interval_seconds: u64, results: &Results) -> Result<()>;
+
+    async fn get_latest_series_for_website(&self, website: &str) -> Result<Option<CheckSeries>>;
+
+    /// Inserts series that are not present yet, identified by their website and start time.
+    /// Returns the amount of inserted series.
+    async fn merge_series(&self, series: &[CheckSeries]) -> Result<usize>;
+
+    /// All websites that are currently configured or managed through the API, including archived
+    /// ones.
+    async fn get_websites(&self) -> Result<Vec<Website>>;
+
+    /// All websites managed through the API with their slug, including archived and paused ones.
+    async fn get_managed_websites(&self) -> Result<Vec<WebsiteConfig>>;
+
+    /// Adds a website managed through the API under its slug, taking over the history of an
+    /// unconfigured website with this slug. Nothing is stored if a configured or managed website
+    /// already has the slug or name.
+    async fn create_managed_website(&self, website: &WebsiteConfig) -> Result<SaveWebsite>;
+
+    /// Adds or replaces a website managed through the API under its slug, taking over the history
+    /// of an unconfigured website with this slug. Nothing is stored if a configured website has
+    /// the slug or another configured or managed website has the name.
+    async fn upsert_managed_website(&self, website: &WebsiteConfig) -> Result<SaveWebsite>;
+
+    /// Stops managing a website through the API, keeping its history like for websites removed
+    /// from the config. Returns `false` if no website with this slug is managed through the API.
+    async fn delete_managed_website(&self, slug: &str) -> Result<bool>;
+
+    /// Pauses or resumes a configured or managed website. Returns `false` if there is no such
+    /// website.
+    async fn set_paused(&self, slug: &str, paused: bool) -> Result<bool>;
+
+    /// The time from the first to the last recorded check of a website.
+    async fn get_history_bounds(&self, website: &str) -> Result<Option<Range<DateTime<Utc>>>>;
+
+    /// All series of a website overlapping with `range`, ordered by time.
+    async fn get_checks_series_in_range(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<CheckSeries>>;
+
+    /// Like [`Storage::get_checks_series_in_range`], but yields the series as they are fetched.
+    fn stream_checks_series_in_range<'a>(
+        &'a self,
+        website: &'a str,
+        range: Range<DateTime<Utc>>,
+    ) -> BoxStream<'a, Result<CheckSeries>>;
+
+    /// All aggregates of a website overlapping with `range`, ordered by time.
+    async fn get_checks_aggregates_in_range(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<CheckAggregate>>;
+
+    /// Slices `range` into `bins` equally long bins and counts the OK, not OK and paused series and
+    /// aggregates overlapping with each of them.
+    async fn get_bins(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+        bins: usize,
+    ) -> Result<Vec<Bin>>;
+
+    /// The time in milliseconds a website was OK and paused within `range`. Aggregates that only
+    /// partly overlap with `range` count with their overlapping share.
+    async fn get_state_millis(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<StateMillis>;
+
+    /// The last time a website was seen OK.
+    async fn get_last_ok(&self, website: &str) -> Result<Option<DateTime<Utc>>>;
+
+    /// Rolls up all series that ended before `cutoff` into hourly aggregates and deletes them.
+    async fn rollup_series(&self, cutoff: DateTime<Utc>) -> Result<usize>;
+
+    /// Rolls up all aggregates of the `from` resolution that started before `cutoff` into
+    /// aggregates of the `to` resolution and deletes them.
+    async fn rollup_aggregates(
+        &self,
+        from: Resolution,
+        to: Resolution,
+        cutoff: DateTime<Utc>,
+    ) -> Result<usize>;
+
+    /// Deletes all series and aggregates that are older than `cutoff`.
+    async fn delete_before(&self, cutoff: DateTime<Utc>) -> Result<()>;
+
+    /// Stores a new incident with its first update and returns its id.
+    async fn create_incident(&self, incident: &NewIncident) -> Result<i64>;
+
+    /// Appends an update to the timeline of an incident. Returns `false` if there is no incident
+    /// with this id.
+    async fn add_incident_update(&self, incident: i64, update: &IncidentUpdate) -> Result<bool>;
+
+    async fn get_incident(&self, incident: i64) -> Result<Option<Incident>>;
+
+    /// All incidents that aren't resolved or were updated after `since`, most recently updated
+    /// first.
+    async fn get_incidents(&self, since: DateTime<Utc>) -> Result<Vec<Incident>>;
+}
+
+/// Opens the storage backend matching the scheme of `db_url`. `postgres://` and `postgresql://`
+/// URLs are opened with Postgres, `memory:` keeps everything in memory and everything else is
+/// treated as an SQLite database.
+/// With `read_only`, the database must already exist and all writes fail.
+pub async fn open(db_url: &str, read_only: bool) -> Result<Arc<dyn Storage>> {
+    if db_url == "memory:" {
+        Ok(Arc::new(MemoryStorage::new()))
+    } else if db_url.starts_with("postgres://") || db_url.starts_with("postgresql://") {
+        Ok(Arc::new(PostgresStorage::open(db_url, read_only).await?))
+    } else {
+        Ok(Arc::new(SqliteStorage::open(db_url, read_only).await?))
+    }
+}
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct CheckSeries {
+    pub id: i32,
+    pub request_time_range_start: chrono::DateTime<Utc>,
+    pub request_time_range_end: chrono::DateTime<Utc>,
+    pub website: String,
+    pub result: CheckState,
+    #[sqlx(flatten)]
+    pub latency: LatencySummary,
+}
+
+/// Response times of all checks merged into a series or aggregate.
+#[derive(sqlx::FromRow, Debug, Clone, Copy, Default)]
+pub struct LatencySummary {
+    #[sqlx(rename = "latency_count")]
+    pub count: i64,
+    #[sqlx(rename = "latency_sum_ms")]
+    pub sum_ms: i64,
+    #[sqlx(rename = "latency_min_ms")]
+    pub min_ms: Option<i64>,
+    #[sqlx(rename = "latency_max_ms")]
+    pub max_ms: Option<i64>,
+}
+
+impl LatencySummary {
+    pub fn from_sample(latency_ms: Option<i64>) -> Self {
+        let mut summary = Self::default();
+        if let Some(latency_ms) = latency_ms {
+            summary.add_sample(latency_ms);
+        }
+        summary
+    }
+
+    pub fn add_sample(&mut self, latency_ms: i64) {
+        self.merge(&Self {
+            count: 1,
+            sum_ms: latency_ms,
+            min_ms: Some(latency_ms),
+            max_ms: Some(latency_ms),
+        });
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        fn merge_opt(a: Option<i64>, b: Option<i64>, f: fn(i64, i64) -> i64) -> Option<i64> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(f(a, b)),
+                (a, b) => a.or(b),
+            }
+        }
+
+        self.count += other.count;
+        self.sum_ms += other.sum_ms;
+        self.min_ms = merge_opt(self.min_ms, other.min_ms, std::cmp::min);
+        self.max_ms = merge_opt(self.max_ms, other.max_ms, std::cmp::max);
+    }
+
+    pub fn average_ms(&self) -> Option<i64> {
+        self.sum_ms.checked_div(self.count)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, sqlx::Type)]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+pub enum Resolution {
+    Hour,
+    Day,
+}
+
+impl Resolution {
+    pub fn seconds(self) -> i64 {
+        match self {
+            Self::Hour => 60 * 60,
+            Self::Day => 24 * 60 * 60,
+        }
+    }
+
+    /// The start of the bucket that `time` falls into.
+    pub fn bucket_start(self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let timestamp = time.timestamp();
+        let start = timestamp - timestamp.rem_euclid(self.seconds());
+        Utc.timestamp_opt(start, 0).unwrap()
+    }
+}
+
+/// Checks of a website rolled up into a fixed time bucket by the retention task.
+#[derive(sqlx::FromRow, Clone)]
+pub struct CheckAggregate {
+    pub website: String,
+    pub resolution: Resolution,
+    pub bucket_start: chrono::DateTime<Utc>,
+    pub ok_millis: i64,
+    pub not_ok_millis: i64,
+    /// The amount of OK series that overlapped with this bucket.
+    pub ok_series: i64,
+    /// The amount of not OK series that overlapped with this bucket.
+    pub not_ok_series: i64,
+    pub paused_millis: i64,
+    /// The amount of paused series that overlapped with this bucket.
+    pub paused_series: i64,
+    #[sqlx(flatten)]
+    pub latency: LatencySummary,
+}
+
+impl CheckAggregate {
+    pub fn new(website: &str, resolution: Resolution, bucket_start: DateTime<Utc>) -> Self {
+        Self {
+            website: website.to_owned(),
+            resolution,
+            bucket_start,
+            ok_millis: 0,
+            not_ok_millis: 0,
+            ok_series: 0,
+            not_ok_series: 0,
+            paused_millis: 0,
+            paused_series: 0,
+            latency: LatencySummary::default(),
+        }
+    }
+
+    pub fn bucket_end(&self) -> DateTime<Utc> {
+        self.bucket_start + chrono::Duration::seconds(self.resolution.seconds())
+    }
+
+    /// The time in milliseconds the website was in `state` within this bucket.
+    pub fn millis(&self, state: CheckState) -> i64 {
+        match state {
+            CheckState::Ok => self.ok_millis,
+            CheckState::NotOk => self.not_ok_millis,
+            CheckState::Paused => self.paused_millis,
+        }
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.ok_millis += other.ok_millis;
+        self.not_ok_millis += other.not_ok_millis;
+        self.ok_series += other.ok_series;
+        self.not_ok_series += other.not_ok_series;
+        self.paused_millis += other.paused_millis;
+        self.paused_series += other.paused_series;
+        self.latency.merge(&other.latency);
+    }
+}
+
+/// The longest time span an aggregate can cover, used to find aggregates overlapping a range.
+fn max_aggregate_span() -> chrono::Duration {
+    chrono::Duration::seconds(Resolution::Day.seconds())
+}
+
+/// Combines the bounds of the series with the first and last aggregate of a website.
+fn history_bounds(
+    (series_start, series_end): (Option<DateTime<Utc>>, Option<DateTime<Utc>>),
+    first_aggregate: Option<CheckAggregate>,
+    last_aggregate: Option<CheckAggregate>,
+) -> Option<Range<DateTime<Utc>>> {
+    let start = match (series_start, first_aggregate) {
+        (Some(series), Some(aggregate)) => Some(std::cmp::min(series, aggregate.bucket_start)),
+        (series, aggregate) => series.or(aggregate.map(|aggregate| aggregate.bucket_start)),
+    };
+    let end = match (series_end, last_aggregate) {
+        (Some(series), Some(aggregate)) => Some(std::cmp::max(series, aggregate.bucket_end())),
+        (series, aggregate) => series.or(aggregate.map(|aggregate| aggregate.bucket_end())),
+    };
+
+    start.zip(end).map(|(start, end)| start..end)
+}
+
+/// The time a website spent in some states, see [`Storage::get_state_millis`].
+#[derive(sqlx::FromRow, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateMillis {
+    pub ok_millis: i64,
+    pub paused_millis: i64,
+}
+
+/// A slice of the history of a website, see [`Storage::get_bins`].
+#[derive(sqlx::FromRow, Debug, Clone, Copy)]
+pub struct Bin {
+    pub idx: i64,
+    /// The amount of OK series and aggregates overlapping with this bin.
+    pub ok_series: i64,
+    /// The amount of not OK series and aggregates overlapping with this bin.
+    pub not_ok_series: i64,
+    /// The amount of paused series and aggregates overlapping with this bin.
+    pub paused_series: i64,
+}
+
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct Website {
+    pub slug: String,
+    pub name: String,
+    pub archived: bool,
+    #[sqlx(rename = "group_name")]
+    pub group: Option<String>,
+    pub tags: sqlx::types::Json<Vec<String>>,
+    /// Private websites are only shown to users with the `private` scope.
+    pub public: bool,
+    /// Whether the website is managed through the API instead of the config.
+    pub managed: bool,
+    /// Whether the website was paused with the CLI or API. Websites can also be paused in the
+    /// config.
+    pub paused: bool,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    sqlx::Type,
+    serde::Serialize,
+    serde::Deserialize,
+    clap::ValueEnum,
+)]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Some users notice problems.
+    Minor,
+    /// Important features are unavailable.
+    Major,
+    /// Everything is unavailable.
+    Critical,
+}
+
+impl Severity {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Minor => "minor",
+            Self::Major => "major",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+/// The stage of an incident, set by each update.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    sqlx::Type,
+    serde::Serialize,
+    serde::Deserialize,
+    clap::ValueEnum,
+)]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentStatus {
+    Investigating,
+    Identified,
+    Monitoring,
+    Resolved,
+}
+
+impl IncidentStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Investigating => "Investigating",
+            Self::Identified => "Identified",
+            Self::Monitoring => "Monitoring",
+            Self::Resolved => "Resolved",
+        }
+    }
+}
+
+/// A problem reported by hand, with a timeline of updates.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Incident {
+    pub id: i64,
+    pub title: String,
+    pub severity: Severity,
+    /// The slugs of the affected websites, none if it isn't about specific websites.
+    pub websites: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    /// All updates, oldest first. There is always at least one.
+    pub updates: Vec<IncidentUpdate>,
+}
+
+impl Incident {
+    /// The status of the latest update.
+    pub fn status(&self) -> IncidentStatus {
+        self.latest_update().status
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        self.status() == IncidentStatus::Resolved
+    }
+
+    pub fn latest_update(&self) -> &IncidentUpdate {
+        self.updates
+            .last()
+            .expect("incidents have at least one update")
+    }
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, serde::Serialize)]
+pub struct IncidentUpdate {
+    pub status: IncidentStatus,
+    pub message: String,
+    pub time: DateTime<Utc>,
+}
+
+/// An incident before it is stored, see [`Storage::create_incident`].
+#[derive(Debug, Clone)]
+pub struct NewIncident {
+    pub title: String,
+    pub severity: Severity,
+    pub websites: Vec<String>,
+    pub update: IncidentUpdate,
+}
+
+/// An incident as stored in the database, without its updates.
+#[derive(sqlx::FromRow)]
+struct IncidentRow {
+    id: i64,
+    title: String,
+    severity: Severity,
+    websites: sqlx::types::Json<Vec<String>>,
+    created_at: DateTime<Utc>,
+}
+
+impl IncidentRow {
+    fn with_updates(self, updates: Vec<IncidentUpdate>) -> Incident {
+        Incident {
+            id: self.id,
+            title: self.title,
+            severity: self.severity,
+            websites: self.websites.0,
+            created_at: self.created_at,
+            updates,
+        }
+    }
+}
+
+/// An update as stored in the database, with the incident it belongs to.
+#[derive(sqlx::FromRow)]
+struct IncidentUpdateRow {
+    incident_id: i64,
+    #[sqlx(flatten)]
+    update: IncidentUpdate,
+}
+
+/// Attaches the updates to their incidents, keeping the order of both.
+fn with_incident_updates(rows: Vec<IncidentRow>, updates: Vec<IncidentUpdateRow>) -> Vec<Incident> {
+    let mut by_incident = HashMap::<i64, Vec<IncidentUpdate>>::new();
+    for row in updates {
+        by_incident
+            .entry(row.incident_id)
+            .or_default()
+            .push(row.update);
+    }
+
+    rows.into_iter()
+        .map(|row| {
+            let updates = by_incident.remove(&row.id).unwrap_or_default();
+            row.with_updates(updates)
+        })
+        .collect()
+}
+
+/// Whether a website managed through the API was stored, see
+/// [`Storage::create_managed_website`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveWebsite {
+    Saved,
+    /// Another website has the slug.
+    SlugTaken,
+    /// Another configured or managed website has the name.
+    NameTaken,
+}
+
+/// Turns the affected rows of storing a website managed through the API into a [`SaveWebsite`].
+/// Violating the unique index on the names of websites means that the name is taken.
+fn save_website_result(
+    rows_affected: std::result::Result<u64, sqlx::Error>,
+    website: &WebsiteConfig,
+) -> Result<SaveWebsite> {
+    match rows_affected {
+        Ok(0) => Ok(SaveWebsite::SlugTaken),
+        Ok(_) => Ok(SaveWebsite::Saved),
+        Err(err)
+            if err
+                .as_database_error()
+                .is_some_and(|err| err.is_unique_violation()) =>
+        {
+            Ok(SaveWebsite::NameTaken)
+        }
+        Err(err) => Err(err).wrap_err_with(|| format!("saving website {}", website.name)),
+    }
+}
+
+/// A website managed through the API as stored in the database.
+#[derive(sqlx::FromRow)]
+struct ManagedWebsiteRow {
+    slug: String,
+    name: String,
+    url: String,
+    archived: bool,
+    paused: bool,
+    group_name: Option<String>,
+    tags: sqlx::types::Json<Vec<String>>,
+    public: bool,
+}
+
+impl ManagedWebsiteRow {
+    fn into_config(self) -> Result<WebsiteConfig> {
+        let url = self
+            .url
+            .parse()
+            .wrap_err_with(|| format!("parsing URL of website {}", self.slug))?;
+        Ok(WebsiteConfig {
+            name: self.name,
+            url,
+            slug: Some(self.slug),
+            previous_names: Vec::new(),
+            archived: self.archived,
+            paused: self.paused,
+            group: self.group_name,
+            tags: self.tags.0,
+            public: self.public,
+        })
+    }
+}
+
+/// A stored website as far as resolving the slugs of configured websites is concerned.
+#[derive(sqlx::FromRow, Clone)]
+struct StoredWebsite {
+    slug: String,
+    name: String,
+    configured: bool,
+    managed: bool,
+}
+
+/// Fills in the slug of every configured website, resolving renames via `previous_names` against
+/// the `stored` websites, where configured ones win over unconfigured ones with the same name.
+/// Fails if two websites resolve to the same slug or a website conflicts with one managed through
+/// the API.
+fn resolve_slugs(websites: &mut [WebsiteConfig], mut stored: Vec<StoredWebsite>) -> Result<()> {
+    for website in &mut stored {
+        website.configured = false;
+    }
+
+    let mut seen_slugs = HashSet::new();
+    for website in websites.iter_mut() {
+        let slug = match &website.slug {
+            Some(slug) => slug.clone(),
+            None => std::iter::once(&website.name)
+                .chain(&website.previous_names)
+                .find_map(|name| {
+                    stored
+                        .iter()
+                        .filter(|existing| existing.name == *name)
+                        .max_by_key(|existing| existing.configured)
+                        .map(|existing| existing.slug.clone())
+                })
+                .unwrap_or_else(|| website.name.clone()),
+        };
+
+        if !seen_slugs.insert(slug.clone()) {
+            return Err(eyre::eyre!(
+                "website {} resolves to the slug `{slug}`, which is already used by another website",
+                website.name
+            ));
+        }
+
+        let managed = stored.iter().find(|existing| {
+            existing.managed && (existing.slug == slug || existing.name == website.name)
+        });
+        if let Some(managed) = managed {
+            return Err(eyre::eyre!(
+                "website {} conflicts with the website `{}` managed through the API, delete it there first",
+                website.name,
+                managed.slug
+            ));
+        }
+
+        // Later websites must see the new name, like they would in the database.
+        match stored.iter_mut().find(|existing| existing.slug == slug) {
+            Some(existing) => {
+                existing.name = website.name.clone();
+                existing.configured = true;
+            }
+            None => stored.push(StoredWebsite {
+                slug: slug.clone(),
+                name: website.name.clone(),
+                configured: true,
+                managed: false,
+            }),
+        }
+
+        website.slug = Some(slug);
+    }
+
+    Ok(())
+}
+
+/// Logs the errors of inserting the results of single websites, failing if there were any.
+fn insert_errors(errors: Vec<eyre::Report>) -> Result<()> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    for err in errors {
+        error!(?err);
+    }
+    Err(eyre::eyre!("error inserting results"))
+}
+
+/// Whether a check continues the latest series of its website instead of starting a new one.
+fn continues_series(
+    latest: &CheckSeries,
+    interval_seconds: u64,
+    check: &CheckResult,
+) -> Result<bool> {
+    let threshold = chrono::Duration::from_std(Duration::from_secs(interval_seconds * 5))
+        .wrap_err("cannot create threshold, interval_seconds too high or low")?;
+
+    let deadline = latest
+        .request_time_range_end
+        .checked_add_signed(threshold)
+        .wrap_err("latest series too far in the future")?;
+
+    Ok(latest.result == check.state && check.time < deadline)
+}
+
+/// Appends a check to `table`, extending the latest series of its website if the state didn't
+/// change.
+pub fn insert_single_result_series_in_memory(
+    table: &mut Vec<CheckSeries>,
+    latest_cache: &mut HashMap<String, usize>,
+    interval_seconds: u64,
+    website: &str,
+    check: &CheckResult,
+) -> Result<()> {
+    let latest = latest_cache.get(website).map(|idx| &mut table[*idx]);
+    let continues = match &latest {
+        Some(latest) => continues_series(latest, interval_seconds, check)?,
+        None => false,
+    };
+
+    match latest {
+        Some(latest) if continues => {
+            latest.request_time_range_end = check.time;
+            latest
+                .latency
+                .merge(&LatencySummary::from_sample(check.latency_ms));
+        }
+        _ => {
+            let idx = table.len();
+            table.push(CheckSeries {
+                id: 0,
+                request_time_range_start: check.time,
+                request_time_range_end: check.time,
+                website: website.to_owned(),
+                result: check.state,
+                latency: LatencySummary::from_sample(check.latency_ms),
+            });
+            *latest_cache.entry(website.to_owned()).or_default() = idx;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,891 @@
+use std::{ops::Range, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use eyre::{Context, Result};
+use futures_util::{stream::BoxStream, StreamExt, TryStreamExt};
+use sqlx::{migrate::Migrator, postgres::PgConnectOptions, types::Json, Pool, Postgres};
+
+static MIGRATOR: Migrator = sqlx::migrate!("migrations/postgres");
+
+use super::{
+    continues_series, history_bounds, insert_errors, max_aggregate_span, resolve_slugs,
+    save_website_result, with_incident_updates, Bin, CheckAggregate, CheckSeries, Incident,
+    IncidentRow, IncidentUpdate, IncidentUpdateRow, LatencySummary, ManagedWebsiteRow, NewIncident,
+    Resolution, SaveWebsite, StateMillis, Storage, StoredWebsite, Website,
+};
+use crate::{
+    client::{CheckResult, Results},
+    config::WebsiteConfig,
+};
+
+pub struct PostgresStorage {
+    pool: Pool<Postgres>,
+}
+
+impl PostgresStorage {
+    pub async fn open(db_url: &str, read_only: bool) -> Result<Self> {
+        let pool = open_db(db_url, read_only).await?;
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for PostgresStorage {
+    async fn migrate(&self, _interval_seconds: u64) -> Result<()> {
+        MIGRATOR
+            .run(&self.pool)
+            .await
+            .wrap_err("running migrations")
+    }
+
+    async fn sync_websites(&self, websites: &mut [WebsiteConfig]) -> Result<()> {
+        sync_websites(&self.pool, websites).await
+    }
+
+    async fn insert_results_series(&self, interval_seconds: u64, results: &Results) -> Result<()> {
+        insert_results_series(&self.pool, interval_seconds, results).await
+    }
+
+    async fn get_latest_series_for_website(&self, website: &str) -> Result<Option<CheckSeries>> {
+        let mut trans = self.pool.begin().await.wrap_err("starting transaction")?;
+        get_latest_series_for_website(&mut trans, website).await
+    }
+
+    async fn merge_series(&self, series: &[CheckSeries]) -> Result<usize> {
+        merge_series(&self.pool, series).await
+    }
+
+    async fn get_websites(&self) -> Result<Vec<Website>> {
+        get_websites(&self.pool).await
+    }
+
+    async fn get_managed_websites(&self) -> Result<Vec<WebsiteConfig>> {
+        get_managed_websites(&self.pool).await
+    }
+
+    async fn create_managed_website(&self, website: &WebsiteConfig) -> Result<SaveWebsite> {
+        save_managed_website(&self.pool, website, false).await
+    }
+
+    async fn upsert_managed_website(&self, website: &WebsiteConfig) -> Result<SaveWebsite> {
+        save_managed_website(&self.pool, website, true).await
+    }
+
+    async fn delete_managed_website(&self, slug: &str) -> Result<bool> {
+        delete_managed_website(&self.pool, slug).await
+    }
+
+    async fn set_paused(&self, slug: &str, paused: bool) -> Result<bool> {
+        set_paused(&self.pool, slug, paused).await
+    }
+
+    async fn get_history_bounds(&self, website: &str) -> Result<Option<Range<DateTime<Utc>>>> {
+        get_history_bounds(&self.pool, website).await
+    }
+
+    async fn get_checks_series_in_range(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<CheckSeries>> {
+        get_checks_series_in_range(&self.pool, website, range).await
+    }
+
+    fn stream_checks_series_in_range<'a>(
+        &'a self,
+        website: &'a str,
+        range: Range<DateTime<Utc>>,
+    ) -> BoxStream<'a, Result<CheckSeries>> {
+        stream_checks_series_in_range(&self.pool, website, range)
+    }
+
+    async fn get_checks_aggregates_in_range(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<CheckAggregate>> {
+        get_checks_aggregates_in_range(&self.pool, website, range).await
+    }
+
+    async fn get_bins(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+        bins: usize,
+    ) -> Result<Vec<Bin>> {
+        get_bins(&self.pool, website, range, bins).await
+    }
+
+    async fn get_state_millis(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<StateMillis> {
+        get_state_millis(&self.pool, website, range).await
+    }
+
+    async fn get_last_ok(&self, website: &str) -> Result<Option<DateTime<Utc>>> {
+        get_last_ok(&self.pool, website).await
+    }
+
+    async fn rollup_series(&self, cutoff: DateTime<Utc>) -> Result<usize> {
+        rollup_series(&self.pool, cutoff).await
+    }
+
+    async fn rollup_aggregates(
+        &self,
+        from: Resolution,
+        to: Resolution,
+        cutoff: DateTime<Utc>,
+    ) -> Result<usize> {
+        rollup_aggregates(&self.pool, from, to, cutoff).await
+    }
+
+    async fn delete_before(&self, cutoff: DateTime<Utc>) -> Result<()> {
+        delete_before(&self.pool, cutoff).await
+    }
+
+    async fn create_incident(&self, incident: &NewIncident) -> Result<i64> {
+        create_incident(&self.pool, incident).await
+    }
+
+    async fn add_incident_update(&self, incident: i64, update: &IncidentUpdate) -> Result<bool> {
+        add_incident_update(&self.pool, incident, update).await
+    }
+
+    async fn get_incident(&self, incident: i64) -> Result<Option<Incident>> {
+        get_incident(&self.pool, incident).await
+    }
+
+    async fn get_incidents(&self, since: DateTime<Utc>) -> Result<Vec<Incident>> {
+        get_incidents(&self.pool, since).await
+    }
+}
+
+async fn open_db(db_url: &str, read_only: bool) -> Result<Pool<Postgres>> {
+    let mut opts = PgConnectOptions::from_str(db_url).wrap_err("parsing database URL")?;
+    if read_only {
+        opts = opts.options([("default_transaction_read_only", "on")]);
+    }
+
+    Pool::connect_with(opts)
+        .await
+        .wrap_err("opening postgres database")
+}
+
+async fn insert_results_series(
+    db: &Pool<Postgres>,
+    interval_seconds: u64,
+    results: &Results,
+) -> Result<()> {
+    let mut errors = Vec::new();
+    for (website, check) in results.states.iter() {
+        let mut trans = db.begin().await.wrap_err("starting transaction")?;
+        let result =
+            insert_single_result_series(&mut trans, interval_seconds, website, check).await;
+        if let Err(err) = result {
+            errors.push(err);
+        } else {
+            trans.commit().await.wrap_err("comitting transaction")?;
+        }
+    }
+
+    insert_errors(errors)
+}
+
+async fn insert_single_result_series(
+    db: &mut sqlx::Transaction<'_, Postgres>,
+    interval_seconds: u64,
+    website: &str,
+    check: &CheckResult,
+) -> Result<()> {
+    let latest = get_latest_series_for_website(db, website)
+        .await
+        .wrap_err("getting the latest series record")?;
+
+    match latest {
+        Some(latest) if continues_series(&latest, interval_seconds, check)? => {
+            let mut latency = latest.latency;
+            latency.merge(&LatencySummary::from_sample(check.latency_ms));
+
+            sqlx::query(
+                "UPDATE checks_series
+                SET request_time_range_end = $1, latency_count = $2, latency_sum_ms = $3, latency_min_ms = $4, latency_max_ms = $5
+                WHERE id = $6",
+            )
+            .bind(check.time)
+            .bind(latency.count)
+            .bind(latency.sum_ms)
+            .bind(latency.min_ms)
+            .bind(latency.max_ms)
+            .bind(latest.id)
+            .execute(&mut **db)
+            .await
+            .wrap_err_with(|| format!("updating series record for {website}"))
+            .map(drop)
+        }
+        _ => {
+            let latency = LatencySummary::from_sample(check.latency_ms);
+
+            sqlx::query(
+                "INSERT INTO checks_series (request_time_range_start, request_time_range_end, website, result,
+                    latency_count, latency_sum_ms, latency_min_ms, latency_max_ms)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            )
+            .bind(check.time)
+            .bind(check.time)
+            .bind(website)
+            .bind(check.state)
+            .bind(latency.count)
+            .bind(latency.sum_ms)
+            .bind(latency.min_ms)
+            .bind(latency.max_ms)
+            .execute(&mut **db)
+            .await
+            .wrap_err_with(|| format!("inserting new series record for {website}"))
+            .map(drop)
+        }
+    }
+}
+
+async fn merge_series(db: &Pool<Postgres>, series: &[CheckSeries]) -> Result<usize> {
+    let mut trans = db.begin().await.wrap_err("starting transaction")?;
+
+    let mut inserted = 0;
+    for series in series {
+        sqlx::query(
+            "INSERT INTO websites (slug, name) VALUES ($1, $1) ON CONFLICT (slug) DO NOTHING",
+        )
+        .bind(&series.website)
+        .execute(&mut *trans)
+        .await
+        .wrap_err_with(|| format!("saving website {}", series.website))?;
+
+        let result = sqlx::query(
+            "INSERT INTO checks_series (request_time_range_start, request_time_range_end, website, result,
+                latency_count, latency_sum_ms, latency_min_ms, latency_max_ms)
+            SELECT $1, $2, $3, $4, $5, $6, $7, $8
+            WHERE NOT EXISTS (
+                SELECT 1 FROM checks_series WHERE website = $3 AND request_time_range_start = $1
+            )",
+        )
+        .bind(series.request_time_range_start)
+        .bind(series.request_time_range_end)
+        .bind(&series.website)
+        .bind(series.result)
+        .bind(series.latency.count)
+        .bind(series.latency.sum_ms)
+        .bind(series.latency.min_ms)
+        .bind(series.latency.max_ms)
+        .execute(&mut *trans)
+        .await
+        .wrap_err_with(|| format!("inserting series for {}", series.website))?;
+
+        inserted += result.rows_affected() as usize;
+    }
+
+    trans.commit().await.wrap_err("committing transaction")?;
+
+    Ok(inserted)
+}
+
+/// Converts a timestamp column into milliseconds since the unix epoch.
+macro_rules! sql_millis {
+    ($column:literal) => {
+        concat!("(EXTRACT(EPOCH FROM ", $column, ") * 1000)::FLOAT8")
+    };
+}
+
+async fn sync_websites(db: &Pool<Postgres>, websites: &mut [WebsiteConfig]) -> Result<()> {
+    let mut trans = db.begin().await.wrap_err("starting transaction")?;
+
+    sqlx::query("UPDATE websites SET configured = FALSE")
+        .execute(&mut *trans)
+        .await
+        .wrap_err("resetting configured websites")?;
+
+    let stored =
+        sqlx::query_as::<_, StoredWebsite>("SELECT slug, name, configured, managed FROM websites")
+            .fetch_all(&mut *trans)
+            .await
+            .wrap_err("getting stored websites")?;
+    resolve_slugs(websites, stored)?;
+
+    for website in websites.iter() {
+        sqlx::query(
+            "INSERT INTO websites (slug, name, configured, archived, group_name, tags, public)
+            VALUES ($1, $2, TRUE, $3, $4, $5, $6)
+            ON CONFLICT (slug) DO UPDATE SET name = excluded.name, configured = TRUE,
+                archived = excluded.archived, group_name = excluded.group_name, tags = excluded.tags,
+                public = excluded.public",
+        )
+        .bind(website.slug())
+        .bind(&website.name)
+        .bind(website.archived)
+        .bind(&website.group)
+        .bind(Json(&website.tags))
+        .bind(website.public)
+        .execute(&mut *trans)
+        .await
+        .wrap_err_with(|| format!("saving website {}", website.name))?;
+    }
+
+    trans.commit().await.wrap_err("committing transaction")
+}
+
+async fn get_websites(db: &Pool<Postgres>) -> Result<Vec<Website>> {
+    sqlx::query_as::<_, Website>(
+        "SELECT slug, name, archived, group_name, tags, public, managed, paused FROM websites
+        WHERE configured OR managed
+        ORDER BY name",
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("getting websites")
+}
+
+async fn get_managed_websites(db: &Pool<Postgres>) -> Result<Vec<WebsiteConfig>> {
+    sqlx::query_as::<_, ManagedWebsiteRow>(
+        "SELECT slug, name, url, archived, paused, group_name, tags, public FROM websites
+        WHERE managed
+        ORDER BY name",
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("getting managed websites")?
+    .into_iter()
+    .map(ManagedWebsiteRow::into_config)
+    .collect()
+}
+
+/// Adds a website managed through the API, or replaces one with `replace`. Websites without
+/// history and unconfigured ones are taken over, configured ones never.
+async fn save_managed_website(
+    db: &Pool<Postgres>,
+    website: &WebsiteConfig,
+    replace: bool,
+) -> Result<SaveWebsite> {
+    let takes_over = if replace {
+        "NOT websites.configured"
+    } else {
+        "NOT websites.configured AND NOT websites.managed"
+    };
+    let result = sqlx::query(&format!(
+        "INSERT INTO websites
+            (slug, name, configured, archived, group_name, tags, public, managed, url, paused)
+        VALUES ($1, $2, FALSE, $3, $4, $5, $6, TRUE, $7, $8)
+        ON CONFLICT (slug) DO UPDATE SET name = excluded.name, archived = excluded.archived,
+            group_name = excluded.group_name, tags = excluded.tags, public = excluded.public,
+            managed = TRUE, url = excluded.url, paused = excluded.paused
+        WHERE {takes_over}"
+    ))
+    .bind(website.slug())
+    .bind(&website.name)
+    .bind(website.archived)
+    .bind(&website.group)
+    .bind(Json(&website.tags))
+    .bind(website.public)
+    .bind(website.url.as_str())
+    .bind(website.paused)
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected());
+
+    save_website_result(result, website)
+}
+
+async fn delete_managed_website(db: &Pool<Postgres>, slug: &str) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE websites SET managed = FALSE, paused = FALSE WHERE slug = $1 AND managed",
+    )
+    .bind(slug)
+    .execute(db)
+    .await
+    .wrap_err_with(|| format!("deleting website {slug}"))?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+async fn set_paused(db: &Pool<Postgres>, slug: &str, paused: bool) -> Result<bool> {
+    let result =
+        sqlx::query("UPDATE websites SET paused = $1 WHERE slug = $2 AND (configured OR managed)")
+            .bind(paused)
+            .bind(slug)
+            .execute(db)
+            .await
+            .wrap_err_with(|| format!("pausing website {slug}"))?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+async fn get_history_bounds(
+    db: &Pool<Postgres>,
+    website: &str,
+) -> Result<Option<Range<DateTime<Utc>>>> {
+    let series = sqlx::query_as::<_, (Option<DateTime<Utc>>, Option<DateTime<Utc>>)>(
+        "SELECT MIN(request_time_range_start), MAX(request_time_range_end)
+        FROM checks_series
+        WHERE website = $1",
+    )
+    .bind(website)
+    .fetch_one(db)
+    .await
+    .wrap_err_with(|| format!("getting series bounds for {website}"))?;
+
+    let first_aggregate = get_edge_aggregate(db, website, "ASC").await?;
+    let last_aggregate = get_edge_aggregate(db, website, "DESC").await?;
+
+    Ok(history_bounds(series, first_aggregate, last_aggregate))
+}
+
+async fn get_edge_aggregate(
+    db: &Pool<Postgres>,
+    website: &str,
+    order: &str,
+) -> Result<Option<CheckAggregate>> {
+    sqlx::query_as::<_, CheckAggregate>(&format!(
+        "SELECT website, resolution, bucket_start, ok_millis, not_ok_millis, ok_series, not_ok_series,
+            paused_millis, paused_series, latency_count, latency_sum_ms, latency_min_ms, latency_max_ms
+        FROM checks_aggregate
+        WHERE website = $1
+        ORDER BY bucket_start {order}
+        LIMIT 1"
+    ))
+    .bind(website)
+    .fetch_optional(db)
+    .await
+    .wrap_err_with(|| format!("getting aggregate bounds for {website}"))
+}
+
+async fn get_checks_series_in_range(
+    db: &Pool<Postgres>,
+    website: &str,
+    range: Range<DateTime<Utc>>,
+) -> Result<Vec<CheckSeries>> {
+    stream_checks_series_in_range(db, website, range)
+        .try_collect()
+        .await
+}
+
+fn stream_checks_series_in_range<'a>(
+    db: &'a Pool<Postgres>,
+    website: &'a str,
+    range: Range<DateTime<Utc>>,
+) -> BoxStream<'a, Result<CheckSeries>> {
+    sqlx::query_as::<_, CheckSeries>(
+        "SELECT id, request_time_range_start, request_time_range_end, website, result,
+            latency_count, latency_sum_ms, latency_min_ms, latency_max_ms
+        FROM checks_series
+        WHERE website = $1 AND request_time_range_end >= $2 AND request_time_range_start <= $3
+        ORDER BY request_time_range_start",
+    )
+    .bind(website)
+    .bind(range.start)
+    .bind(range.end)
+    .fetch(db)
+    .map(move |series| series.wrap_err_with(|| format!("getting series for {website}")))
+    .boxed()
+}
+
+async fn get_checks_aggregates_in_range(
+    db: &Pool<Postgres>,
+    website: &str,
+    range: Range<DateTime<Utc>>,
+) -> Result<Vec<CheckAggregate>> {
+    let aggregates = sqlx::query_as::<_, CheckAggregate>(
+        "SELECT website, resolution, bucket_start, ok_millis, not_ok_millis, ok_series, not_ok_series,
+            paused_millis, paused_series, latency_count, latency_sum_ms, latency_min_ms, latency_max_ms
+        FROM checks_aggregate
+        WHERE website = $1 AND bucket_start >= $2 AND bucket_start <= $3
+        ORDER BY bucket_start",
+    )
+    .bind(website)
+    .bind(range.start - max_aggregate_span())
+    .bind(range.end)
+    .fetch_all(db)
+    .await
+    .wrap_err_with(|| format!("getting aggregates for {website}"))?;
+
+    Ok(aggregates
+        .into_iter()
+        .filter(|aggregate| aggregate.bucket_end() >= range.start)
+        .collect())
+}
+
+async fn get_bins(
+    db: &Pool<Postgres>,
+    website: &str,
+    range: Range<DateTime<Utc>>,
+    bins: usize,
+) -> Result<Vec<Bin>> {
+    let range_start_millis = range.start.timestamp_millis() as f64;
+    let bin_millis =
+        (range.end.timestamp_millis() - range.start.timestamp_millis()) as f64 / bins as f64;
+
+    sqlx::query_as::<_, Bin>(concat!(
+        "WITH segments(start_millis, end_millis, ok_series, not_ok_series, paused_series) AS (
+            SELECT ",
+        sql_millis!("request_time_range_start"),
+        ", ",
+        sql_millis!("request_time_range_end"),
+        ",
+                CASE result WHEN 'ok' THEN 1 ELSE 0 END::BIGINT,
+                CASE result WHEN 'not_ok' THEN 1 ELSE 0 END::BIGINT,
+                CASE result WHEN 'paused' THEN 1 ELSE 0 END::BIGINT
+            FROM checks_series
+            WHERE website = $2 AND request_time_range_end >= $3 AND request_time_range_start <= $4
+            UNION ALL
+            SELECT ",
+        sql_millis!("bucket_start"),
+        ", ",
+        sql_millis!("bucket_start"),
+        " + CASE resolution WHEN 'hour' THEN 3600000 ELSE 86400000 END, ok_series, not_ok_series,
+                paused_series
+            FROM checks_aggregate
+            WHERE website = $2 AND bucket_start >= $5 AND bucket_start <= $4
+        )
+        SELECT bins.idx::BIGINT AS idx,
+            COALESCE(SUM(segments.ok_series), 0)::BIGINT AS ok_series,
+            COALESCE(SUM(segments.not_ok_series), 0)::BIGINT AS not_ok_series,
+            COALESCE(SUM(segments.paused_series), 0)::BIGINT AS paused_series
+        FROM generate_series(0, $1 - 1) AS bins(idx)
+        LEFT JOIN segments
+            ON segments.start_millis <= $6 + (bins.idx + 1) * $7
+            AND segments.end_millis >= $6 + bins.idx * $7
+        GROUP BY bins.idx
+        ORDER BY bins.idx"
+    ))
+    .bind(bins as i64)
+    .bind(website)
+    .bind(range.start)
+    .bind(range.end)
+    .bind(range.start - max_aggregate_span())
+    .bind(range_start_millis)
+    .bind(bin_millis)
+    .fetch_all(db)
+    .await
+    .wrap_err_with(|| format!("computing bins for {website}"))
+}
+
+/// The time in milliseconds a website was OK and paused within `range`, counting aggregates that
+/// only partly overlap with `range` with their overlapping share.
+async fn get_state_millis(
+    db: &Pool<Postgres>,
+    website: &str,
+    range: Range<DateTime<Utc>>,
+) -> Result<StateMillis> {
+    sqlx::query_as::<_, StateMillis>(concat!(
+        "WITH
+            series(result, overlap_millis) AS (
+                SELECT result, GREATEST(0, ROUND(LEAST(",
+        sql_millis!("request_time_range_end"),
+        ", $5) - GREATEST(",
+        sql_millis!("request_time_range_start"),
+        ", $4))::BIGINT)
+                FROM checks_series
+                WHERE website = $1 AND request_time_range_end >= $2 AND request_time_range_start <= $3
+            ),
+            buckets(ok_millis, paused_millis, start_millis, bucket_millis) AS (
+                SELECT ok_millis, paused_millis, ",
+        sql_millis!("bucket_start"),
+        ", CASE resolution WHEN 'hour' THEN 3600000 ELSE 86400000 END::BIGINT
+                FROM checks_aggregate
+                WHERE website = $1 AND bucket_start >= $6 AND bucket_start <= $3
+            ),
+            aggregates(ok_millis, paused_millis, bucket_millis, overlap_millis) AS (
+                SELECT ok_millis, paused_millis, bucket_millis,
+                    LEAST(bucket_millis, GREATEST(0, ROUND(
+                        LEAST(start_millis + bucket_millis, $5) - GREATEST(start_millis, $4)
+                    )::BIGINT))
+                FROM buckets
+            ),
+            segments(ok_millis, paused_millis) AS (
+                SELECT CASE result WHEN 'ok' THEN overlap_millis ELSE 0 END,
+                    CASE result WHEN 'paused' THEN overlap_millis ELSE 0 END
+                FROM series
+                UNION ALL
+                SELECT ok_millis * overlap_millis / bucket_millis,
+                    paused_millis * overlap_millis / bucket_millis
+                FROM aggregates
+            )
+        SELECT COALESCE(SUM(ok_millis), 0)::BIGINT AS ok_millis,
+            COALESCE(SUM(paused_millis), 0)::BIGINT AS paused_millis
+        FROM segments"
+    ))
+    .bind(website)
+    .bind(range.start)
+    .bind(range.end)
+    .bind(range.start.timestamp_millis() as f64)
+    .bind(range.end.timestamp_millis() as f64)
+    .bind(range.start - max_aggregate_span())
+    .fetch_one(db)
+    .await
+    .wrap_err_with(|| format!("computing uptime of {website}"))
+}
+
+async fn get_last_ok(db: &Pool<Postgres>, website: &str) -> Result<Option<DateTime<Utc>>> {
+    let series = sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+        "SELECT MAX(request_time_range_end) FROM checks_series WHERE website = $1 AND result = 'ok'",
+    )
+    .bind(website)
+    .fetch_one(db)
+    .await
+    .wrap_err_with(|| format!("getting last OK series for {website}"))?;
+
+    if series.is_some() {
+        return Ok(series);
+    }
+
+    let aggregate = sqlx::query_as::<_, CheckAggregate>(
+        "SELECT website, resolution, bucket_start, ok_millis, not_ok_millis, ok_series, not_ok_series,
+            paused_millis, paused_series, latency_count, latency_sum_ms, latency_min_ms, latency_max_ms
+        FROM checks_aggregate
+        WHERE website = $1 AND ok_series > 0
+        ORDER BY bucket_start DESC
+        LIMIT 1",
+    )
+    .bind(website)
+    .fetch_optional(db)
+    .await
+    .wrap_err_with(|| format!("getting last OK aggregate for {website}"))?;
+
+    Ok(aggregate.map(|aggregate| aggregate.bucket_end()))
+}
+
+async fn get_latest_series_for_website(
+    db: &mut sqlx::Transaction<'_, Postgres>,
+    website: &str,
+) -> Result<Option<CheckSeries>> {
+    sqlx::query_as::<_, CheckSeries>(
+        "SELECT id, request_time_range_start, request_time_range_end, website, result,
+            latency_count, latency_sum_ms, latency_min_ms, latency_max_ms
+        FROM checks_series
+        WHERE website = $1
+        ORDER BY request_time_range_end DESC
+        LIMIT 1",
+    )
+    .bind(website)
+    .fetch_optional(&mut **db)
+    .await
+    .wrap_err("getting the latest series")
+}
+
+async fn rollup_series(db: &Pool<Postgres>, cutoff: DateTime<Utc>) -> Result<usize> {
+    let mut trans = db.begin().await.wrap_err("starting transaction")?;
+
+    let series = sqlx::query_as::<_, CheckSeries>(
+        "DELETE FROM checks_series
+        WHERE request_time_range_end < $1
+        RETURNING id, request_time_range_start, request_time_range_end, website, result,
+            latency_count, latency_sum_ms, latency_min_ms, latency_max_ms",
+    )
+    .bind(cutoff)
+    .fetch_all(&mut *trans)
+    .await
+    .wrap_err("deleting old series")?;
+
+    let aggregates = crate::retention::aggregate_series(&series);
+    for aggregate in &aggregates {
+        upsert_aggregate(&mut trans, aggregate).await?;
+    }
+
+    trans.commit().await.wrap_err("committing transaction")?;
+
+    Ok(series.len())
+}
+
+async fn rollup_aggregates(
+    db: &Pool<Postgres>,
+    from: Resolution,
+    to: Resolution,
+    cutoff: DateTime<Utc>,
+) -> Result<usize> {
+    let mut trans = db.begin().await.wrap_err("starting transaction")?;
+
+    let old = sqlx::query_as::<_, CheckAggregate>(
+        "DELETE FROM checks_aggregate
+        WHERE resolution = $1 AND bucket_start < $2
+        RETURNING website, resolution, bucket_start, ok_millis, not_ok_millis, ok_series, not_ok_series,
+            paused_millis, paused_series, latency_count, latency_sum_ms, latency_min_ms, latency_max_ms",
+    )
+    .bind(from)
+    .bind(cutoff)
+    .fetch_all(&mut *trans)
+    .await
+    .wrap_err("deleting old aggregates")?;
+
+    let aggregates = crate::retention::aggregate_aggregates(&old, to);
+    for aggregate in &aggregates {
+        upsert_aggregate(&mut trans, aggregate).await?;
+    }
+
+    trans.commit().await.wrap_err("committing transaction")?;
+
+    Ok(old.len())
+}
+
+async fn delete_before(db: &Pool<Postgres>, cutoff: DateTime<Utc>) -> Result<()> {
+    let mut trans = db.begin().await.wrap_err("starting transaction")?;
+
+    sqlx::query("DELETE FROM checks_series WHERE request_time_range_end < $1")
+        .bind(cutoff)
+        .execute(&mut *trans)
+        .await
+        .wrap_err("deleting expired series")?;
+
+    sqlx::query("DELETE FROM checks_aggregate WHERE bucket_start < $1")
+        .bind(cutoff)
+        .execute(&mut *trans)
+        .await
+        .wrap_err("deleting expired aggregates")?;
+
+    trans.commit().await.wrap_err("committing transaction")
+}
+
+async fn upsert_aggregate(
+    db: &mut sqlx::Transaction<'_, Postgres>,
+    aggregate: &CheckAggregate,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO checks_aggregate (website, resolution, bucket_start, ok_millis, not_ok_millis, ok_series, not_ok_series,
+            paused_millis, paused_series, latency_count, latency_sum_ms, latency_min_ms, latency_max_ms)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        ON CONFLICT (website, resolution, bucket_start) DO UPDATE SET
+            ok_millis = checks_aggregate.ok_millis + excluded.ok_millis,
+            not_ok_millis = checks_aggregate.not_ok_millis + excluded.not_ok_millis,
+            ok_series = checks_aggregate.ok_series + excluded.ok_series,
+            not_ok_series = checks_aggregate.not_ok_series + excluded.not_ok_series,
+            paused_millis = checks_aggregate.paused_millis + excluded.paused_millis,
+            paused_series = checks_aggregate.paused_series + excluded.paused_series,
+            latency_count = checks_aggregate.latency_count + excluded.latency_count,
+            latency_sum_ms = checks_aggregate.latency_sum_ms + excluded.latency_sum_ms,
+            latency_min_ms = LEAST(checks_aggregate.latency_min_ms, excluded.latency_min_ms),
+            latency_max_ms = GREATEST(checks_aggregate.latency_max_ms, excluded.latency_max_ms)",
+    )
+    .bind(&aggregate.website)
+    .bind(aggregate.resolution)
+    .bind(aggregate.bucket_start)
+    .bind(aggregate.ok_millis)
+    .bind(aggregate.not_ok_millis)
+    .bind(aggregate.ok_series)
+    .bind(aggregate.not_ok_series)
+    .bind(aggregate.paused_millis)
+    .bind(aggregate.paused_series)
+    .bind(aggregate.latency.count)
+    .bind(aggregate.latency.sum_ms)
+    .bind(aggregate.latency.min_ms)
+    .bind(aggregate.latency.max_ms)
+    .execute(&mut **db)
+    .await
+    .wrap_err_with(|| format!("upserting aggregate for {}", aggregate.website))
+    .map(drop)
+}
+
+async fn create_incident(db: &Pool<Postgres>, incident: &NewIncident) -> Result<i64> {
+    let mut trans = db.begin().await.wrap_err("starting transaction")?;
+
+    let id = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO incidents (title, severity, websites, created_at, status, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $4) RETURNING id",
+    )
+    .bind(&incident.title)
+    .bind(incident.severity)
+    .bind(Json(&incident.websites))
+    .bind(incident.update.time)
+    .bind(incident.update.status)
+    .fetch_one(&mut *trans)
+    .await
+    .wrap_err("inserting incident")?;
+
+    insert_incident_update(&mut trans, id, &incident.update).await?;
+
+    trans.commit().await.wrap_err("committing transaction")?;
+    Ok(id)
+}
+
+async fn add_incident_update(
+    db: &Pool<Postgres>,
+    incident: i64,
+    update: &IncidentUpdate,
+) -> Result<bool> {
+    let mut trans = db.begin().await.wrap_err("starting transaction")?;
+
+    let updated = sqlx::query("UPDATE incidents SET status = $1, updated_at = $2 WHERE id = $3")
+        .bind(update.status)
+        .bind(update.time)
+        .bind(incident)
+        .execute(&mut *trans)
+        .await
+        .wrap_err_with(|| format!("updating incident {incident}"))?;
+    if updated.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    insert_incident_update(&mut trans, incident, update).await?;
+
+    trans.commit().await.wrap_err("committing transaction")?;
+    Ok(true)
+}
+
+async fn insert_incident_update(
+    db: &mut sqlx::Transaction<'_, Postgres>,
+    incident: i64,
+    update: &IncidentUpdate,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO incident_updates (incident_id, status, message, time) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(incident)
+    .bind(update.status)
+    .bind(&update.message)
+    .bind(update.time)
+    .execute(&mut **db)
+    .await
+    .wrap_err_with(|| format!("inserting update of incident {incident}"))
+    .map(drop)
+}
+
+async fn get_incident(db: &Pool<Postgres>, incident: i64) -> Result<Option<Incident>> {
+    let row = sqlx::query_as::<_, IncidentRow>(
+        "SELECT id, title, severity, websites, created_at FROM incidents WHERE id = $1",
+    )
+    .bind(incident)
+    .fetch_optional(db)
+    .await
+    .wrap_err_with(|| format!("getting incident {incident}"))?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let updates = get_incident_updates(db, &[row.id]).await?;
+    Ok(with_incident_updates(vec![row], updates).pop())
+}
+
+async fn get_incidents(db: &Pool<Postgres>, since: DateTime<Utc>) -> Result<Vec<Incident>> {
+    let rows = sqlx::query_as::<_, IncidentRow>(
+        "SELECT id, title, severity, websites, created_at FROM incidents
+        WHERE status != 'resolved' OR updated_at > $1
+        ORDER BY updated_at DESC, id DESC",
+    )
+    .bind(since)
+    .fetch_all(db)
+    .await
+    .wrap_err("getting incidents")?;
+
+    let ids = rows.iter().map(|row| row.id).collect::<Vec<_>>();
+    let updates = get_incident_updates(db, &ids).await?;
+    Ok(with_incident_updates(rows, updates))
+}
+
+/// The updates of all `ids`, oldest first.
+async fn get_incident_updates(db: &Pool<Postgres>, ids: &[i64]) -> Result<Vec<IncidentUpdateRow>> {
+    sqlx::query_as::<_, IncidentUpdateRow>(
+        "SELECT incident_id, status, message, time FROM incident_updates
+        WHERE incident_id = ANY($1)
+        ORDER BY time, id",
+    )
+    .bind(ids)
+    .fetch_all(db)
+    .await
+    .wrap_err("getting incident updates")
+}
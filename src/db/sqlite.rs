@@ -0,0 +1,979 @@
+use std::{collections::HashMap, ops::Range, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use eyre::{Context, Result};
+use futures_util::{stream::BoxStream, StreamExt, TryStreamExt};
+use sqlx::{migrate::Migrator, sqlite::SqliteConnectOptions, types::Json, Pool, Sqlite};
+
+static MIGRATOR: Migrator = sqlx::migrate!("migrations/sqlite");
+
+use super::{
+    continues_series, history_bounds, insert_errors, insert_single_result_series_in_memory,
+    max_aggregate_span, resolve_slugs, save_website_result, with_incident_updates, Bin,
+    CheckAggregate, CheckSeries, Incident, IncidentRow, IncidentUpdate, IncidentUpdateRow,
+    LatencySummary, ManagedWebsiteRow, NewIncident, Resolution, SaveWebsite, StateMillis, Storage,
+    StoredWebsite, Website,
+};
+use crate::{
+    client::{CheckResult, CheckState, Results},
+    config::WebsiteConfig,
+};
+
+pub struct SqliteStorage {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteStorage {
+    pub async fn open(db_url: &str, read_only: bool) -> Result<Self> {
+        let pool = open_db(db_url, read_only).await?;
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for SqliteStorage {
+    async fn migrate(&self, interval_seconds: u64) -> Result<()> {
+        MIGRATOR
+            .run(&self.pool)
+            .await
+            .wrap_err("running migrations")?;
+
+        migrate_checks(&self.pool, interval_seconds)
+            .await
+            .wrap_err("migrating old checks to series")
+    }
+
+    async fn sync_websites(&self, websites: &mut [WebsiteConfig]) -> Result<()> {
+        sync_websites(&self.pool, websites).await
+    }
+
+    async fn insert_results_series(&self, interval_seconds: u64, results: &Results) -> Result<()> {
+        insert_results_series(&self.pool, interval_seconds, results).await
+    }
+
+    async fn get_latest_series_for_website(&self, website: &str) -> Result<Option<CheckSeries>> {
+        let mut trans = self.pool.begin().await.wrap_err("starting transaction")?;
+        get_latest_series_for_website(&mut trans, website).await
+    }
+
+    async fn merge_series(&self, series: &[CheckSeries]) -> Result<usize> {
+        merge_series(&self.pool, series).await
+    }
+
+    async fn get_websites(&self) -> Result<Vec<Website>> {
+        get_websites(&self.pool).await
+    }
+
+    async fn get_managed_websites(&self) -> Result<Vec<WebsiteConfig>> {
+        get_managed_websites(&self.pool).await
+    }
+
+    async fn create_managed_website(&self, website: &WebsiteConfig) -> Result<SaveWebsite> {
+        save_managed_website(&self.pool, website, false).await
+    }
+
+    async fn upsert_managed_website(&self, website: &WebsiteConfig) -> Result<SaveWebsite> {
+        save_managed_website(&self.pool, website, true).await
+    }
+
+    async fn delete_managed_website(&self, slug: &str) -> Result<bool> {
+        delete_managed_website(&self.pool, slug).await
+    }
+
+    async fn set_paused(&self, slug: &str, paused: bool) -> Result<bool> {
+        set_paused(&self.pool, slug, paused).await
+    }
+
+    async fn get_history_bounds(&self, website: &str) -> Result<Option<Range<DateTime<Utc>>>> {
+        get_history_bounds(&self.pool, website).await
+    }
+
+    async fn get_checks_series_in_range(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<CheckSeries>> {
+        get_checks_series_in_range(&self.pool, website, range).await
+    }
+
+    fn stream_checks_series_in_range<'a>(
+        &'a self,
+        website: &'a str,
+        range: Range<DateTime<Utc>>,
+    ) -> BoxStream<'a, Result<CheckSeries>> {
+        stream_checks_series_in_range(&self.pool, website, range)
+    }
+
+    async fn get_checks_aggregates_in_range(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<CheckAggregate>> {
+        get_checks_aggregates_in_range(&self.pool, website, range).await
+    }
+
+    async fn get_bins(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+        bins: usize,
+    ) -> Result<Vec<Bin>> {
+        get_bins(&self.pool, website, range, bins).await
+    }
+
+    async fn get_state_millis(
+        &self,
+        website: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<StateMillis> {
+        get_state_millis(&self.pool, website, range).await
+    }
+
+    async fn get_last_ok(&self, website: &str) -> Result<Option<DateTime<Utc>>> {
+        get_last_ok(&self.pool, website).await
+    }
+
+    async fn rollup_series(&self, cutoff: DateTime<Utc>) -> Result<usize> {
+        rollup_series(&self.pool, cutoff).await
+    }
+
+    async fn rollup_aggregates(
+        &self,
+        from: Resolution,
+        to: Resolution,
+        cutoff: DateTime<Utc>,
+    ) -> Result<usize> {
+        rollup_aggregates(&self.pool, from, to, cutoff).await
+    }
+
+    async fn delete_before(&self, cutoff: DateTime<Utc>) -> Result<()> {
+        delete_before(&self.pool, cutoff).await
+    }
+
+    async fn create_incident(&self, incident: &NewIncident) -> Result<i64> {
+        create_incident(&self.pool, incident).await
+    }
+
+    async fn add_incident_update(&self, incident: i64, update: &IncidentUpdate) -> Result<bool> {
+        add_incident_update(&self.pool, incident, update).await
+    }
+
+    async fn get_incident(&self, incident: i64) -> Result<Option<Incident>> {
+        get_incident(&self.pool, incident).await
+    }
+
+    async fn get_incidents(&self, since: DateTime<Utc>) -> Result<Vec<Incident>> {
+        get_incidents(&self.pool, since).await
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub struct Check {
+    pub id: i32,
+    pub request_time: chrono::DateTime<Utc>,
+    pub website: String,
+    pub result: CheckState,
+}
+
+async fn open_db(db_url: &str, read_only: bool) -> Result<Pool<Sqlite>> {
+    let db_opts = SqliteConnectOptions::from_str(db_url)
+        .wrap_err("parsing database URL")?
+        .create_if_missing(!read_only)
+        .read_only(read_only);
+
+    Pool::connect_with(db_opts)
+        .await
+        .wrap_err_with(|| format!("opening db from `{}`", db_url))
+}
+
+async fn insert_results_series(
+    db: &Pool<Sqlite>,
+    interval_seconds: u64,
+    results: &Results,
+) -> Result<()> {
+    let mut errors = Vec::new();
+    for (website, check) in results.states.iter() {
+        let mut trans = db.begin().await.wrap_err("starting transaction")?;
+        let result =
+            insert_single_result_series(&mut trans, interval_seconds, website, check).await;
+        if let Err(err) = result {
+            errors.push(err);
+        } else {
+            trans.commit().await.wrap_err("comitting transaction")?;
+        }
+    }
+
+    insert_errors(errors)
+}
+
+async fn insert_single_result_series(
+    db: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    interval_seconds: u64,
+    website: &str,
+    check: &CheckResult,
+) -> Result<()> {
+    let latest = get_latest_series_for_website(db, website)
+        .await
+        .wrap_err("getting the latest series record")?;
+
+    match latest {
+        Some(latest) if continues_series(&latest, interval_seconds, check)? => {
+            let mut latency = latest.latency;
+            latency.merge(&LatencySummary::from_sample(check.latency_ms));
+
+            sqlx::query("UPDATE checks_series SET request_time_range_end = ?, latency_count = ?, latency_sum_ms = ?, latency_min_ms = ?, latency_max_ms = ? WHERE rowid = ?")
+            .bind(check.time)
+            .bind(latency.count)
+            .bind(latency.sum_ms)
+            .bind(latency.min_ms)
+            .bind(latency.max_ms)
+            .bind(latest.id)
+            .execute(&mut **db)
+            .await
+            .wrap_err_with(|| format!("updating series record for {website}"))
+            .map(drop)
+        }
+        _ => {
+            let latency = LatencySummary::from_sample(check.latency_ms);
+
+            sqlx::query("INSERT INTO checks_series (request_time_range_start, request_time_range_end, website, result, latency_count, latency_sum_ms, latency_min_ms, latency_max_ms) VALUES (?, ?, ?, ?, ?, ?, ?, ?);")
+            .bind(check.time)
+            .bind(check.time)
+            .bind(website)
+            .bind(check.state)
+            .bind(latency.count)
+            .bind(latency.sum_ms)
+            .bind(latency.min_ms)
+            .bind(latency.max_ms)
+            .execute(&mut **db)
+            .await
+            .wrap_err_with(|| format!("inserting new series record for {website}"))
+            .map(drop)
+        }
+    }
+}
+
+async fn merge_series(db: &Pool<Sqlite>, series: &[CheckSeries]) -> Result<usize> {
+    let mut trans = db.begin().await.wrap_err("starting transaction")?;
+
+    let mut inserted = 0;
+    for series in series {
+        sqlx::query(
+            "INSERT INTO websites (slug, name) VALUES (?, ?) ON CONFLICT (slug) DO NOTHING",
+        )
+        .bind(&series.website)
+        .bind(&series.website)
+        .execute(&mut *trans)
+        .await
+        .wrap_err_with(|| format!("saving website {}", series.website))?;
+
+        let result = sqlx::query(
+            "INSERT INTO checks_series (request_time_range_start, request_time_range_end, website, result,
+                latency_count, latency_sum_ms, latency_min_ms, latency_max_ms)
+            SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8
+            WHERE NOT EXISTS (
+                SELECT 1 FROM checks_series WHERE website = ?3 AND request_time_range_start = ?1
+            )",
+        )
+        .bind(series.request_time_range_start)
+        .bind(series.request_time_range_end)
+        .bind(&series.website)
+        .bind(series.result)
+        .bind(series.latency.count)
+        .bind(series.latency.sum_ms)
+        .bind(series.latency.min_ms)
+        .bind(series.latency.max_ms)
+        .execute(&mut *trans)
+        .await
+        .wrap_err_with(|| format!("inserting series for {}", series.website))?;
+
+        inserted += result.rows_affected() as usize;
+    }
+
+    trans.commit().await.wrap_err("committing transaction")?;
+
+    Ok(inserted)
+}
+
+async fn get_checks(db: &Pool<Sqlite>) -> Result<Vec<Check>> {
+    sqlx::query_as::<_, Check>("SELECT id, request_time, website, result FROM checks")
+        .fetch_all(db)
+        .await
+        .wrap_err("getting all checks")
+}
+
+/// Converts a timestamp column into milliseconds since the unix epoch.
+macro_rules! sql_millis {
+    ($column:literal) => {
+        concat!("((julianday(", $column, ") - 2440587.5) * 86400000.0)")
+    };
+}
+
+/// Makes sure every configured website has a row in the websites table, resolving renames via
+/// `previous_names`, and fills in the slug of every website.
+/// Websites that are not in the config anymore are marked as unconfigured.
+async fn sync_websites(db: &Pool<Sqlite>, websites: &mut [WebsiteConfig]) -> Result<()> {
+    let mut trans = db.begin().await.wrap_err("starting transaction")?;
+
+    // Writing first takes the write lock right away instead of upgrading a read lock later.
+    sqlx::query("UPDATE websites SET configured = FALSE")
+        .execute(&mut *trans)
+        .await
+        .wrap_err("resetting configured websites")?;
+
+    let stored =
+        sqlx::query_as::<_, StoredWebsite>("SELECT slug, name, configured, managed FROM websites")
+            .fetch_all(&mut *trans)
+            .await
+            .wrap_err("getting stored websites")?;
+    resolve_slugs(websites, stored)?;
+
+    for website in websites.iter() {
+        sqlx::query(
+            "INSERT INTO websites (slug, name, configured, archived, group_name, tags, public)
+            VALUES (?, ?, TRUE, ?, ?, ?, ?)
+            ON CONFLICT (slug) DO UPDATE SET name = excluded.name, configured = TRUE,
+                archived = excluded.archived, group_name = excluded.group_name, tags = excluded.tags,
+                public = excluded.public",
+        )
+        .bind(website.slug())
+        .bind(&website.name)
+        .bind(website.archived)
+        .bind(&website.group)
+        .bind(Json(&website.tags))
+        .bind(website.public)
+        .execute(&mut *trans)
+        .await
+        .wrap_err_with(|| format!("saving website {}", website.name))?;
+    }
+
+    trans.commit().await.wrap_err("committing transaction")
+}
+
+/// All websites that are currently configured or managed through the API, including archived ones.
+async fn get_websites(db: &Pool<Sqlite>) -> Result<Vec<Website>> {
+    sqlx::query_as::<_, Website>(
+        "SELECT slug, name, archived, group_name, tags, public, managed, paused FROM websites
+            WHERE configured OR managed
+            ORDER BY name",
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("getting websites")
+}
+
+/// All websites managed through the API with their slug, including archived and paused ones.
+async fn get_managed_websites(db: &Pool<Sqlite>) -> Result<Vec<WebsiteConfig>> {
+    sqlx::query_as::<_, ManagedWebsiteRow>(
+        "SELECT slug, name, url, archived, paused, group_name, tags, public FROM websites
+            WHERE managed
+            ORDER BY name",
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("getting managed websites")?
+    .into_iter()
+    .map(ManagedWebsiteRow::into_config)
+    .collect()
+}
+
+/// Adds a website managed through the API, or replaces one with `replace`. Websites without
+/// history and unconfigured ones are taken over, configured ones never.
+async fn save_managed_website(
+    db: &Pool<Sqlite>,
+    website: &WebsiteConfig,
+    replace: bool,
+) -> Result<SaveWebsite> {
+    let takes_over = if replace {
+        "NOT websites.configured"
+    } else {
+        "NOT websites.configured AND NOT websites.managed"
+    };
+    let result = sqlx::query(&format!(
+        "INSERT INTO websites
+            (slug, name, configured, archived, group_name, tags, public, managed, url, paused)
+        VALUES (?, ?, FALSE, ?, ?, ?, ?, TRUE, ?, ?)
+        ON CONFLICT (slug) DO UPDATE SET name = excluded.name, archived = excluded.archived,
+            group_name = excluded.group_name, tags = excluded.tags, public = excluded.public,
+            managed = TRUE, url = excluded.url, paused = excluded.paused
+        WHERE {takes_over}"
+    ))
+    .bind(website.slug())
+    .bind(&website.name)
+    .bind(website.archived)
+    .bind(&website.group)
+    .bind(Json(&website.tags))
+    .bind(website.public)
+    .bind(website.url.as_str())
+    .bind(website.paused)
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected());
+
+    save_website_result(result, website)
+}
+
+async fn delete_managed_website(db: &Pool<Sqlite>, slug: &str) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE websites SET managed = FALSE, paused = FALSE WHERE slug = ? AND managed",
+    )
+    .bind(slug)
+    .execute(db)
+    .await
+    .wrap_err_with(|| format!("deleting website {slug}"))?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+async fn set_paused(db: &Pool<Sqlite>, slug: &str, paused: bool) -> Result<bool> {
+    let result =
+        sqlx::query("UPDATE websites SET paused = ? WHERE slug = ? AND (configured OR managed)")
+            .bind(paused)
+            .bind(slug)
+            .execute(db)
+            .await
+            .wrap_err_with(|| format!("pausing website {slug}"))?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// The time from the first to the last recorded check of a website.
+async fn get_history_bounds(
+    db: &Pool<Sqlite>,
+    website: &str,
+) -> Result<Option<Range<DateTime<Utc>>>> {
+    let series = sqlx::query_as::<_, (Option<DateTime<Utc>>, Option<DateTime<Utc>>)>(
+        "SELECT MIN(request_time_range_start), MAX(request_time_range_end)
+            FROM checks_series
+            WHERE website = ?",
+    )
+    .bind(website)
+    .fetch_one(db)
+    .await
+    .wrap_err_with(|| format!("getting series bounds for {website}"))?;
+
+    let first_aggregate = get_edge_aggregate(db, website, "ASC").await?;
+    let last_aggregate = get_edge_aggregate(db, website, "DESC").await?;
+
+    Ok(history_bounds(series, first_aggregate, last_aggregate))
+}
+
+async fn get_edge_aggregate(
+    db: &Pool<Sqlite>,
+    website: &str,
+    order: &str,
+) -> Result<Option<CheckAggregate>> {
+    sqlx::query_as::<_, CheckAggregate>(&format!(
+        "SELECT website, resolution, bucket_start, ok_millis, not_ok_millis, ok_series, not_ok_series,
+            paused_millis, paused_series, latency_count, latency_sum_ms, latency_min_ms, latency_max_ms
+        FROM checks_aggregate
+        WHERE website = ?
+        ORDER BY bucket_start {order}
+        LIMIT 1"
+    ))
+    .bind(website)
+    .fetch_optional(db)
+    .await
+    .wrap_err_with(|| format!("getting aggregate bounds for {website}"))
+}
+
+/// All series of a website overlapping with `range`, ordered by time.
+async fn get_checks_series_in_range(
+    db: &Pool<Sqlite>,
+    website: &str,
+    range: Range<DateTime<Utc>>,
+) -> Result<Vec<CheckSeries>> {
+    stream_checks_series_in_range(db, website, range)
+        .try_collect()
+        .await
+}
+
+/// Like [`get_checks_series_in_range`], but yields the series as they are fetched.
+fn stream_checks_series_in_range<'a>(
+    db: &'a Pool<Sqlite>,
+    website: &'a str,
+    range: Range<DateTime<Utc>>,
+) -> BoxStream<'a, Result<CheckSeries>> {
+    sqlx::query_as::<_, CheckSeries>(
+        "SELECT rowid as id, request_time_range_start, request_time_range_end, website, result,
+            latency_count, latency_sum_ms, latency_min_ms, latency_max_ms
+        FROM checks_series
+        WHERE website = ? AND request_time_range_end >= ? AND request_time_range_start <= ?
+        ORDER BY request_time_range_start",
+    )
+    .bind(website)
+    .bind(range.start)
+    .bind(range.end)
+    .fetch(db)
+    .map(move |series| series.wrap_err_with(|| format!("getting series for {website}")))
+    .boxed()
+}
+
+/// All aggregates of a website overlapping with `range`, ordered by time.
+async fn get_checks_aggregates_in_range(
+    db: &Pool<Sqlite>,
+    website: &str,
+    range: Range<DateTime<Utc>>,
+) -> Result<Vec<CheckAggregate>> {
+    let aggregates = sqlx::query_as::<_, CheckAggregate>(
+        "SELECT website, resolution, bucket_start, ok_millis, not_ok_millis, ok_series, not_ok_series,
+            paused_millis, paused_series, latency_count, latency_sum_ms, latency_min_ms, latency_max_ms
+        FROM checks_aggregate
+        WHERE website = ? AND bucket_start >= ? AND bucket_start <= ?
+        ORDER BY bucket_start",
+    )
+    .bind(website)
+    .bind(range.start - max_aggregate_span())
+    .bind(range.end)
+    .fetch_all(db)
+    .await
+    .wrap_err_with(|| format!("getting aggregates for {website}"))?;
+
+    Ok(aggregates
+        .into_iter()
+        .filter(|aggregate| aggregate.bucket_end() >= range.start)
+        .collect())
+}
+
+/// Slices `range` into `bins` equally long bins and counts the OK, not OK and paused series and
+/// aggregates overlapping with each of them.
+async fn get_bins(
+    db: &Pool<Sqlite>,
+    website: &str,
+    range: Range<DateTime<Utc>>,
+    bins: usize,
+) -> Result<Vec<Bin>> {
+    let range_start_millis = range.start.timestamp_millis() as f64;
+    let bin_millis =
+        (range.end.timestamp_millis() - range.start.timestamp_millis()) as f64 / bins as f64;
+
+    sqlx::query_as::<_, Bin>(concat!(
+        "WITH RECURSIVE
+            bins(idx) AS (SELECT 0 UNION ALL SELECT idx + 1 FROM bins WHERE idx + 1 < ?1),
+            segments(start_millis, end_millis, ok_series, not_ok_series, paused_series) AS (
+                SELECT ",
+        sql_millis!("request_time_range_start"),
+        ", ",
+        sql_millis!("request_time_range_end"),
+        ", result = 'ok', result = 'not_ok', result = 'paused'
+                FROM checks_series
+                WHERE website = ?2 AND request_time_range_end >= ?3 AND request_time_range_start <= ?4
+                UNION ALL
+                SELECT ",
+        sql_millis!("bucket_start"),
+        ", ",
+        sql_millis!("bucket_start"),
+        " + CASE resolution WHEN 'hour' THEN 3600000 ELSE 86400000 END, ok_series, not_ok_series,
+                    paused_series
+                FROM checks_aggregate
+                WHERE website = ?2 AND bucket_start >= ?5 AND bucket_start <= ?4
+            )
+        SELECT bins.idx AS idx,
+            COALESCE(SUM(segments.ok_series), 0) AS ok_series,
+            COALESCE(SUM(segments.not_ok_series), 0) AS not_ok_series,
+            COALESCE(SUM(segments.paused_series), 0) AS paused_series
+        FROM bins
+        LEFT JOIN segments
+            ON segments.start_millis <= ?6 + (bins.idx + 1) * ?7
+            AND segments.end_millis >= ?6 + bins.idx * ?7
+        GROUP BY bins.idx
+        ORDER BY bins.idx"
+    ))
+    .bind(bins as i64)
+    .bind(website)
+    .bind(range.start)
+    .bind(range.end)
+    .bind(range.start - max_aggregate_span())
+    .bind(range_start_millis)
+    .bind(bin_millis)
+    .fetch_all(db)
+    .await
+    .wrap_err_with(|| format!("computing bins for {website}"))
+}
+
+/// The time in milliseconds a website was OK and paused within `range`, counting aggregates that
+/// only partly overlap with `range` with their overlapping share.
+async fn get_state_millis(
+    db: &Pool<Sqlite>,
+    website: &str,
+    range: Range<DateTime<Utc>>,
+) -> Result<StateMillis> {
+    sqlx::query_as::<_, StateMillis>(concat!(
+        "WITH
+            series(result, overlap_millis) AS (
+                SELECT result, MAX(0, CAST(ROUND(MIN(",
+        sql_millis!("request_time_range_end"),
+        ", ?5) - MAX(",
+        sql_millis!("request_time_range_start"),
+        ", ?4)) AS INTEGER))
+                FROM checks_series
+                WHERE website = ?1 AND request_time_range_end >= ?2 AND request_time_range_start <= ?3
+            ),
+            buckets(ok_millis, paused_millis, start_millis, bucket_millis) AS (
+                SELECT ok_millis, paused_millis, ",
+        sql_millis!("bucket_start"),
+        ", CASE resolution WHEN 'hour' THEN 3600000 ELSE 86400000 END
+                FROM checks_aggregate
+                WHERE website = ?1 AND bucket_start >= ?6 AND bucket_start <= ?3
+            ),
+            aggregates(ok_millis, paused_millis, bucket_millis, overlap_millis) AS (
+                SELECT ok_millis, paused_millis, bucket_millis,
+                    MIN(bucket_millis, MAX(0, CAST(ROUND(
+                        MIN(start_millis + bucket_millis, ?5) - MAX(start_millis, ?4)
+                    ) AS INTEGER)))
+                FROM buckets
+            ),
+            segments(ok_millis, paused_millis) AS (
+                SELECT CASE result WHEN 'ok' THEN overlap_millis ELSE 0 END,
+                    CASE result WHEN 'paused' THEN overlap_millis ELSE 0 END
+                FROM series
+                UNION ALL
+                SELECT ok_millis * overlap_millis / bucket_millis,
+                    paused_millis * overlap_millis / bucket_millis
+                FROM aggregates
+            )
+        SELECT COALESCE(SUM(ok_millis), 0) AS ok_millis,
+            COALESCE(SUM(paused_millis), 0) AS paused_millis
+        FROM segments"
+    ))
+    .bind(website)
+    .bind(range.start)
+    .bind(range.end)
+    .bind(range.start.timestamp_millis())
+    .bind(range.end.timestamp_millis())
+    .bind(range.start - max_aggregate_span())
+    .fetch_one(db)
+    .await
+    .wrap_err_with(|| format!("computing uptime of {website}"))
+}
+
+/// The last time a website was seen OK.
+async fn get_last_ok(db: &Pool<Sqlite>, website: &str) -> Result<Option<DateTime<Utc>>> {
+    let series = sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+        "SELECT MAX(request_time_range_end) FROM checks_series WHERE website = ? AND result = 'ok'",
+    )
+    .bind(website)
+    .fetch_one(db)
+    .await
+    .wrap_err_with(|| format!("getting last OK series for {website}"))?;
+
+    if series.is_some() {
+        return Ok(series);
+    }
+
+    let aggregate = sqlx::query_as::<_, CheckAggregate>(
+        "SELECT website, resolution, bucket_start, ok_millis, not_ok_millis, ok_series, not_ok_series,
+            paused_millis, paused_series, latency_count, latency_sum_ms, latency_min_ms, latency_max_ms
+        FROM checks_aggregate
+        WHERE website = ? AND ok_series > 0
+        ORDER BY bucket_start DESC
+        LIMIT 1",
+    )
+    .bind(website)
+    .fetch_optional(db)
+    .await
+    .wrap_err_with(|| format!("getting last OK aggregate for {website}"))?;
+
+    Ok(aggregate.map(|aggregate| aggregate.bucket_end()))
+}
+
+async fn migrate_checks(db: &Pool<Sqlite>, interval_seconds: u64) -> Result<()> {
+    info!("Migrating checks to check_series");
+    let Ok(mut checks) = get_checks(db).await else {
+        return Ok(());
+    };
+    info!("Computing checks");
+    checks.sort_unstable_by_key(|check| check.request_time);
+    let mut table = Vec::new();
+    let mut latest_cache = HashMap::new();
+
+    for check in checks.iter() {
+        let check_result = CheckResult {
+            time: check.request_time,
+            state: check.result,
+            latency_ms: None,
+            reason: None,
+        };
+        insert_single_result_series_in_memory(
+            &mut table,
+            &mut latest_cache,
+            interval_seconds,
+            &check.website,
+            &check_result,
+        )?;
+    }
+
+    info!("Inserting checks");
+    let mut db_trans = db.begin().await.wrap_err("starting transaction")?;
+    for check in table.iter() {
+        sqlx::query("INSERT INTO checks_series (request_time_range_start, request_time_range_end, website, result) VALUES (?, ?, ?, ?);")
+        .bind(check.request_time_range_start)
+        .bind(check.request_time_range_end)
+        .bind(&check.website)
+        .bind(check.result)
+        .execute(&mut *db_trans)
+        .await
+        .wrap_err_with(|| format!("inserting new series record for {}", check.website))?;
+    }
+    info!("Dropping old table");
+
+    sqlx::query("DROP TABLE checks")
+        .execute(&mut *db_trans)
+        .await
+        .wrap_err("dropping table checks")?;
+
+    db_trans.commit().await.wrap_err("committing transaction")?;
+
+    sqlx::query("VACUUM")
+        .execute(db)
+        .await
+        .wrap_err("running vacuum")?;
+
+    Ok(())
+}
+
+async fn get_latest_series_for_website(
+    db: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    website: &str,
+) -> Result<Option<CheckSeries>> {
+    sqlx::query_as::<_, CheckSeries>(
+        "SELECT rowid as id, request_time_range_start, request_time_range_end, website, result,
+            latency_count, latency_sum_ms, latency_min_ms, latency_max_ms
+        FROM checks_series
+        WHERE website = ?
+        ORDER BY request_time_range_end DESC
+        LIMIT 1
+        ",
+    )
+    .bind(website)
+    .fetch_all(&mut **db)
+    .await
+    .wrap_err("getting all checks")
+    .map(|elems| -> Option<CheckSeries> { elems.get(0).cloned() })
+}
+
+/// Rolls up all series that ended before `cutoff` into hourly aggregates and deletes them.
+async fn rollup_series(db: &Pool<Sqlite>, cutoff: DateTime<Utc>) -> Result<usize> {
+    let mut trans = db.begin().await.wrap_err("starting transaction")?;
+
+    // Deleting first takes the write lock right away instead of upgrading a read lock later.
+    let series = sqlx::query_as::<_, CheckSeries>(
+        "DELETE FROM checks_series
+        WHERE request_time_range_end < ?
+        RETURNING rowid as id, request_time_range_start, request_time_range_end, website, result,
+            latency_count, latency_sum_ms, latency_min_ms, latency_max_ms",
+    )
+    .bind(cutoff)
+    .fetch_all(&mut *trans)
+    .await
+    .wrap_err("deleting old series")?;
+
+    let aggregates = crate::retention::aggregate_series(&series);
+    for aggregate in &aggregates {
+        upsert_aggregate(&mut trans, aggregate).await?;
+    }
+
+    trans.commit().await.wrap_err("committing transaction")?;
+
+    Ok(series.len())
+}
+
+/// Rolls up all aggregates of the `from` resolution that started before `cutoff` into aggregates
+/// of the `to` resolution and deletes them.
+async fn rollup_aggregates(
+    db: &Pool<Sqlite>,
+    from: Resolution,
+    to: Resolution,
+    cutoff: DateTime<Utc>,
+) -> Result<usize> {
+    let mut trans = db.begin().await.wrap_err("starting transaction")?;
+
+    let old = sqlx::query_as::<_, CheckAggregate>(
+        "DELETE FROM checks_aggregate
+        WHERE resolution = ? AND bucket_start < ?
+        RETURNING website, resolution, bucket_start, ok_millis, not_ok_millis, ok_series, not_ok_series,
+            paused_millis, paused_series, latency_count, latency_sum_ms, latency_min_ms, latency_max_ms",
+    )
+    .bind(from)
+    .bind(cutoff)
+    .fetch_all(&mut *trans)
+    .await
+    .wrap_err("deleting old aggregates")?;
+
+    let aggregates = crate::retention::aggregate_aggregates(&old, to);
+    for aggregate in &aggregates {
+        upsert_aggregate(&mut trans, aggregate).await?;
+    }
+
+    trans.commit().await.wrap_err("committing transaction")?;
+
+    Ok(old.len())
+}
+
+/// Deletes all series and aggregates that are older than `cutoff`.
+async fn delete_before(db: &Pool<Sqlite>, cutoff: DateTime<Utc>) -> Result<()> {
+    let mut trans = db.begin().await.wrap_err("starting transaction")?;
+
+    sqlx::query("DELETE FROM checks_series WHERE request_time_range_end < ?")
+        .bind(cutoff)
+        .execute(&mut *trans)
+        .await
+        .wrap_err("deleting expired series")?;
+
+    sqlx::query("DELETE FROM checks_aggregate WHERE bucket_start < ?")
+        .bind(cutoff)
+        .execute(&mut *trans)
+        .await
+        .wrap_err("deleting expired aggregates")?;
+
+    trans.commit().await.wrap_err("committing transaction")
+}
+
+async fn upsert_aggregate(
+    db: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    aggregate: &CheckAggregate,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO checks_aggregate (website, resolution, bucket_start, ok_millis, not_ok_millis, ok_series, not_ok_series,
+            paused_millis, paused_series, latency_count, latency_sum_ms, latency_min_ms, latency_max_ms)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT (website, resolution, bucket_start) DO UPDATE SET
+            ok_millis = ok_millis + excluded.ok_millis,
+            not_ok_millis = not_ok_millis + excluded.not_ok_millis,
+            ok_series = ok_series + excluded.ok_series,
+            not_ok_series = not_ok_series + excluded.not_ok_series,
+            paused_millis = paused_millis + excluded.paused_millis,
+            paused_series = paused_series + excluded.paused_series,
+            latency_count = latency_count + excluded.latency_count,
+            latency_sum_ms = latency_sum_ms + excluded.latency_sum_ms,
+            latency_min_ms = min(coalesce(latency_min_ms, excluded.latency_min_ms), coalesce(excluded.latency_min_ms, latency_min_ms)),
+            latency_max_ms = max(coalesce(latency_max_ms, excluded.latency_max_ms), coalesce(excluded.latency_max_ms, latency_max_ms))",
+    )
+    .bind(&aggregate.website)
+    .bind(aggregate.resolution)
+    .bind(aggregate.bucket_start)
+    .bind(aggregate.ok_millis)
+    .bind(aggregate.not_ok_millis)
+    .bind(aggregate.ok_series)
+    .bind(aggregate.not_ok_series)
+    .bind(aggregate.paused_millis)
+    .bind(aggregate.paused_series)
+    .bind(aggregate.latency.count)
+    .bind(aggregate.latency.sum_ms)
+    .bind(aggregate.latency.min_ms)
+    .bind(aggregate.latency.max_ms)
+    .execute(&mut **db)
+    .await
+    .wrap_err_with(|| format!("upserting aggregate for {}", aggregate.website))
+    .map(drop)
+}
+
+async fn create_incident(db: &Pool<Sqlite>, incident: &NewIncident) -> Result<i64> {
+    let mut trans = db.begin().await.wrap_err("starting transaction")?;
+
+    let id = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO incidents (title, severity, websites, created_at, status, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?) RETURNING id",
+    )
+    .bind(&incident.title)
+    .bind(incident.severity)
+    .bind(Json(&incident.websites))
+    .bind(incident.update.time)
+    .bind(incident.update.status)
+    .bind(incident.update.time)
+    .fetch_one(&mut *trans)
+    .await
+    .wrap_err("inserting incident")?;
+
+    insert_incident_update(&mut trans, id, &incident.update).await?;
+
+    trans.commit().await.wrap_err("committing transaction")?;
+    Ok(id)
+}
+
+async fn add_incident_update(
+    db: &Pool<Sqlite>,
+    incident: i64,
+    update: &IncidentUpdate,
+) -> Result<bool> {
+    let mut trans = db.begin().await.wrap_err("starting transaction")?;
+
+    let updated = sqlx::query("UPDATE incidents SET status = ?, updated_at = ? WHERE id = ?")
+        .bind(update.status)
+        .bind(update.time)
+        .bind(incident)
+        .execute(&mut *trans)
+        .await
+        .wrap_err_with(|| format!("updating incident {incident}"))?;
+    if updated.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    insert_incident_update(&mut trans, incident, update).await?;
+
+    trans.commit().await.wrap_err("committing transaction")?;
+    Ok(true)
+}
+
+async fn insert_incident_update(
+    db: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    incident: i64,
+    update: &IncidentUpdate,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO incident_updates (incident_id, status, message, time) VALUES (?, ?, ?, ?)",
+    )
+    .bind(incident)
+    .bind(update.status)
+    .bind(&update.message)
+    .bind(update.time)
+    .execute(&mut **db)
+    .await
+    .wrap_err_with(|| format!("inserting update of incident {incident}"))
+    .map(drop)
+}
+
+async fn get_incident(db: &Pool<Sqlite>, incident: i64) -> Result<Option<Incident>> {
+    let row = sqlx::query_as::<_, IncidentRow>(
+        "SELECT id, title, severity, websites, created_at FROM incidents WHERE id = ?",
+    )
+    .bind(incident)
+    .fetch_optional(db)
+    .await
+    .wrap_err_with(|| format!("getting incident {incident}"))?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let updates = get_incident_updates(db, &[row.id]).await?;
+    Ok(with_incident_updates(vec![row], updates).pop())
+}
+
+async fn get_incidents(db: &Pool<Sqlite>, since: DateTime<Utc>) -> Result<Vec<Incident>> {
+    let rows = sqlx::query_as::<_, IncidentRow>(
+        "SELECT id, title, severity, websites, created_at FROM incidents
+        WHERE status != 'resolved' OR updated_at > ?
+        ORDER BY updated_at DESC, id DESC",
+    )
+    .bind(since)
+    .fetch_all(db)
+    .await
+    .wrap_err("getting incidents")?;
+
+    let ids = rows.iter().map(|row| row.id).collect::<Vec<_>>();
+    let updates = get_incident_updates(db, &ids).await?;
+    Ok(with_incident_updates(rows, updates))
+}
+
+/// The updates of all `ids`, oldest first.
+async fn get_incident_updates(db: &Pool<Sqlite>, ids: &[i64]) -> Result<Vec<IncidentUpdateRow>> {
+    sqlx::query_as::<_, IncidentUpdateRow>(
+        "SELECT incident_id, status, message, time FROM incident_updates
+        WHERE incident_id IN (SELECT value FROM json_each(?))
+        ORDER BY time, id",
+    )
+    .bind(Json(ids))
+    .fetch_all(db)
+    .await
+    .wrap_err("getting incident updates")
+}
@@ -0,0 +1,814 @@
+//! Tests of the storage backends. Every test runs against in-memory storage and a fresh SQLite
+//! database, and against Postgres if `UPTIME_TEST_POSTGRES_URL` is set. Each test gets its own
+//! schema in that Postgres database, which is dropped and recreated on every run.
+
+use std::{collections::BTreeMap, future::Future, path::PathBuf, sync::Arc};
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use eyre::{Context, Result};
+
+use super::{
+    CheckSeries, IncidentStatus, IncidentUpdate, LatencySummary, MemoryStorage, NewIncident,
+    PostgresStorage, Resolution, SaveWebsite, Severity, SqliteStorage, StateMillis, Storage,
+};
+use crate::{
+    client::{CheckResult, CheckState, Results},
+    config::WebsiteConfig,
+};
+
+/// Runs `test` against a fresh storage of every backend. `name` must be unique across all tests.
+async fn with_each_backend<F, Fut>(name: &str, test: F)
+where
+    F: Fn(Arc<dyn Storage>) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let sqlite_path =
+        std::env::temp_dir().join(format!("uptime-test-{name}-{}.db", std::process::id()));
+    let mut backends: Vec<(&str, Arc<dyn Storage>)> = vec![
+        ("memory", Arc::new(MemoryStorage::new())),
+        ("sqlite", sqlite(&sqlite_path).await.unwrap()),
+    ];
+    match postgres(name).await.unwrap() {
+        Some(postgres) => backends.push(("postgres", postgres)),
+        None => eprintln!("UPTIME_TEST_POSTGRES_URL is not set, skipping Postgres"),
+    }
+
+    for (backend, db) in backends {
+        if let Err(err) = test(db).await {
+            panic!("{name} failed with {backend}: {err:?}");
+        }
+    }
+
+    let _ = std::fs::remove_file(sqlite_path);
+}
+
+async fn sqlite(path: &PathBuf) -> Result<Arc<dyn Storage>> {
+    let _ = std::fs::remove_file(path);
+    let db = SqliteStorage::open(&format!("sqlite://{}", path.display()), false).await?;
+    db.migrate(30).await?;
+    Ok(Arc::new(db))
+}
+
+async fn postgres(name: &str) -> Result<Option<Arc<dyn Storage>>> {
+    let Ok(url) = std::env::var("UPTIME_TEST_POSTGRES_URL") else {
+        return Ok(None);
+    };
+
+    let schema = format!("uptime_test_{name}");
+    let pool = sqlx::PgPool::connect(&url)
+        .await
+        .wrap_err("connecting to UPTIME_TEST_POSTGRES_URL")?;
+    sqlx::query(&format!("DROP SCHEMA IF EXISTS {schema} CASCADE"))
+        .execute(&pool)
+        .await?;
+    sqlx::query(&format!("CREATE SCHEMA {schema}"))
+        .execute(&pool)
+        .await?;
+    pool.close().await;
+
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let url = format!("{url}{separator}options[search_path]={schema}");
+    let db = PostgresStorage::open(&url, false).await?;
+    db.migrate(30).await?;
+    Ok(Some(Arc::new(db)))
+}
+
+fn website(json: serde_json::Value) -> WebsiteConfig {
+    serde_json::from_value(json).unwrap()
+}
+
+/// Midnight of the day all history in these tests starts on, plus `minutes`.
+fn at(minutes: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2023, 9, 1, 0, 0, 0).unwrap() + Duration::minutes(minutes)
+}
+
+/// Stores a website named `a` to attach history to.
+async fn website_a(db: &dyn Storage) -> Result<()> {
+    let mut config = vec![website(
+        serde_json::json!({ "name": "a", "url": "https://a.example.com" }),
+    )];
+    db.sync_websites(&mut config).await
+}
+
+fn series(state: CheckState, minutes: std::ops::Range<i64>) -> CheckSeries {
+    CheckSeries {
+        id: 0,
+        request_time_range_start: at(minutes.start),
+        request_time_range_end: at(minutes.end),
+        website: "a".into(),
+        result: state,
+        latency: LatencySummary::default(),
+    }
+}
+
+/// The state, start and end in minutes of every series of `a` on the day of [`at`].
+async fn series_of_a(db: &dyn Storage) -> Result<Vec<(CheckState, i64, i64)>> {
+    Ok(db
+        .get_checks_series_in_range("a", at(0)..at(24 * 60))
+        .await?
+        .into_iter()
+        .map(|series| {
+            (
+                series.result,
+                (series.request_time_range_start - at(0)).num_minutes(),
+                (series.request_time_range_end - at(0)).num_minutes(),
+            )
+        })
+        .collect())
+}
+
+/// The slugs and names of all stored websites.
+async fn websites(db: &dyn Storage) -> Result<Vec<(String, String)>> {
+    Ok(db
+        .get_websites()
+        .await?
+        .into_iter()
+        .map(|website| (website.slug, website.name))
+        .collect())
+}
+
+#[tokio::test]
+async fn sync_websites_keeps_slugs_across_renames() {
+    with_each_backend("sync_renames", |db| async move {
+        let mut config = vec![website(
+            serde_json::json!({ "name": "api", "url": "https://api.example.com" }),
+        )];
+        db.sync_websites(&mut config).await?;
+        assert_eq!(config[0].slug.as_deref(), Some("api"));
+
+        let mut config = vec![website(serde_json::json!({
+            "name": "API",
+            "url": "https://api.example.com",
+            "previous_names": ["api"],
+        }))];
+        db.sync_websites(&mut config).await?;
+        assert_eq!(config[0].slug.as_deref(), Some("api"));
+        assert_eq!(websites(&*db).await?, [("api".into(), "API".into())]);
+
+        // Without it, the old name is gone and the website starts over.
+        let mut config = vec![website(
+            serde_json::json!({ "name": "api v2", "url": "https://api.example.com" }),
+        )];
+        db.sync_websites(&mut config).await?;
+        assert_eq!(config[0].slug.as_deref(), Some("api v2"));
+        assert_eq!(websites(&*db).await?, [("api v2".into(), "api v2".into())]);
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn sync_websites_rejects_conflicts() {
+    with_each_backend("sync_conflicts", |db| async move {
+        let mut config = vec![website(
+            serde_json::json!({ "name": "a", "url": "https://a.example.com" }),
+        )];
+        db.sync_websites(&mut config).await?;
+
+        let mut duplicate = vec![
+            website(
+                serde_json::json!({ "name": "b", "url": "https://b.example.com", "slug": "x" }),
+            ),
+            website(
+                serde_json::json!({ "name": "c", "url": "https://c.example.com", "slug": "x" }),
+            ),
+        ];
+        assert!(db.sync_websites(&mut duplicate).await.is_err());
+
+        let managed = website(serde_json::json!({
+            "name": "managed",
+            "url": "https://managed.example.com",
+            "slug": "managed",
+        }));
+        assert_eq!(
+            db.upsert_managed_website(&managed).await?,
+            SaveWebsite::Saved
+        );
+        let mut conflicting = vec![website(
+            serde_json::json!({ "name": "managed", "url": "https://other.example.com" }),
+        )];
+        assert!(db.sync_websites(&mut conflicting).await.is_err());
+
+        // Failed syncs don't change anything.
+        assert_eq!(
+            websites(&*db).await?,
+            [
+                ("a".into(), "a".into()),
+                ("managed".into(), "managed".into())
+            ]
+        );
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn managed_websites() {
+    with_each_backend("managed_websites", |db| async move {
+        let mut config = vec![website(
+            serde_json::json!({ "name": "configured", "url": "https://a.example.com" }),
+        )];
+        db.sync_websites(&mut config).await?;
+
+        let managed = website(serde_json::json!({
+            "name": "managed",
+            "url": "https://managed.example.com",
+            "slug": "managed",
+            "tags": ["api"],
+        }));
+        assert_eq!(
+            db.upsert_managed_website(&managed).await?,
+            SaveWebsite::Saved
+        );
+        let stored = db.get_managed_websites().await?;
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].url.as_str(), "https://managed.example.com/");
+        assert_eq!(stored[0].tags, ["api"]);
+
+        let taken = website(serde_json::json!({
+            "name": "taken",
+            "url": "https://taken.example.com",
+            "slug": "configured",
+        }));
+        assert_eq!(
+            db.upsert_managed_website(&taken).await?,
+            SaveWebsite::SlugTaken
+        );
+
+        assert!(db.set_paused("managed", true).await?);
+        assert!(db.get_managed_websites().await?[0].paused);
+        assert!(!db.set_paused("unknown", true).await?);
+
+        assert!(db.delete_managed_website("managed").await?);
+        assert!(!db.delete_managed_website("managed").await?);
+        assert!(db.get_managed_websites().await?.is_empty());
+        assert_eq!(
+            websites(&*db).await?,
+            [("configured".into(), "configured".into())]
+        );
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn create_managed_website_never_replaces() {
+    with_each_backend("create_managed", |db| async move {
+        let mut config = vec![website(
+            serde_json::json!({ "name": "configured", "url": "https://a.example.com" }),
+        )];
+        db.sync_websites(&mut config).await?;
+
+        let first = website(serde_json::json!({
+            "name": "first",
+            "url": "https://first.example.com",
+            "slug": "managed",
+        }));
+        assert_eq!(db.create_managed_website(&first).await?, SaveWebsite::Saved);
+
+        let second = website(serde_json::json!({
+            "name": "second",
+            "url": "https://second.example.com",
+            "slug": "managed",
+        }));
+        assert_eq!(
+            db.create_managed_website(&second).await?,
+            SaveWebsite::SlugTaken
+        );
+        let stored = db.get_managed_websites().await?;
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].name, "first");
+        assert_eq!(stored[0].url.as_str(), "https://first.example.com/");
+
+        // Names are unique among configured and managed websites, whichever way they are saved.
+        let same_name = website(serde_json::json!({
+            "name": "configured",
+            "url": "https://other.example.com",
+            "slug": "other",
+        }));
+        assert_eq!(
+            db.create_managed_website(&same_name).await?,
+            SaveWebsite::NameTaken
+        );
+        assert_eq!(
+            db.upsert_managed_website(&same_name).await?,
+            SaveWebsite::NameTaken
+        );
+        assert_eq!(
+            websites(&*db).await?,
+            [
+                ("configured".into(), "configured".into()),
+                ("managed".into(), "first".into())
+            ]
+        );
+
+        // Deleted websites keep their history, which a new website with the slug takes over.
+        assert!(db.delete_managed_website("managed").await?);
+        assert_eq!(
+            db.create_managed_website(&second).await?,
+            SaveWebsite::Saved
+        );
+        assert_eq!(db.get_managed_websites().await?[0].name, "second");
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn insert_results_series_continues_series() {
+    with_each_backend("insert_series", |db| async move {
+        website_a(&*db).await?;
+
+        let checks = [
+            (0, CheckState::Ok, Some(100)),
+            (1, CheckState::Ok, Some(300)),
+            (2, CheckState::NotOk, None),
+            (3, CheckState::Ok, Some(200)),
+            (4, CheckState::Ok, None),
+        ];
+        for (minute, state, latency_ms) in checks {
+            let results = Results {
+                states: BTreeMap::from([(
+                    "a".to_owned(),
+                    CheckResult {
+                        time: at(minute),
+                        state,
+                        latency_ms,
+                        reason: None,
+                    },
+                )]),
+            };
+            db.insert_results_series(60, &results).await?;
+        }
+
+        assert_eq!(
+            series_of_a(&*db).await?,
+            [
+                (CheckState::Ok, 0, 1),
+                (CheckState::NotOk, 2, 2),
+                (CheckState::Ok, 3, 4)
+            ]
+        );
+        let latest = db.get_latest_series_for_website("a").await?.unwrap();
+        assert_eq!(latest.request_time_range_start, at(3));
+        assert_eq!(latest.latency.count, 1);
+        assert_eq!(latest.latency.average_ms(), Some(200));
+
+        let first = &db.get_checks_series_in_range("a", at(0)..at(1)).await?[0];
+        assert_eq!(first.latency.count, 2);
+        assert_eq!(first.latency.min_ms, Some(100));
+        assert_eq!(first.latency.max_ms, Some(300));
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn rollup_keeps_the_time_in_each_state() {
+    with_each_backend("rollup", |db| async move {
+        website_a(&*db).await?;
+        db.merge_series(&[
+            series(CheckState::Ok, 10..50),
+            series(CheckState::NotOk, 50..90),
+            series(CheckState::Paused, 90..100),
+            series(CheckState::Ok, 100..130),
+        ])
+        .await?;
+
+        // Only series that ended before the cutoff are rolled up.
+        assert_eq!(db.rollup_series(at(120)).await?, 3);
+        assert_eq!(series_of_a(&*db).await?, [(CheckState::Ok, 100, 130)]);
+
+        let hours = db
+            .get_checks_aggregates_in_range("a", at(0)..at(24 * 60))
+            .await?;
+        let hours = hours
+            .iter()
+            .map(|aggregate| {
+                (
+                    aggregate.resolution,
+                    (aggregate.bucket_start - at(0)).num_minutes(),
+                    aggregate.ok_millis / 60_000,
+                    aggregate.not_ok_millis / 60_000,
+                    aggregate.paused_millis / 60_000,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hours,
+            [
+                (Resolution::Hour, 0, 40, 10, 0),
+                (Resolution::Hour, 60, 0, 30, 10)
+            ]
+        );
+
+        assert_eq!(
+            db.rollup_aggregates(Resolution::Hour, Resolution::Day, at(24 * 60))
+                .await?,
+            2
+        );
+        let days = db
+            .get_checks_aggregates_in_range("a", at(0)..at(24 * 60))
+            .await?;
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].resolution, Resolution::Day);
+        assert_eq!(days[0].bucket_start, at(0));
+        assert_eq!(days[0].ok_millis, 40 * 60_000);
+        assert_eq!(days[0].not_ok_millis, 40 * 60_000);
+        assert_eq!(days[0].paused_millis, 10 * 60_000);
+
+        // The rolled up series still count for the whole day.
+        assert_eq!(
+            db.get_state_millis("a", at(0)..at(24 * 60))
+                .await?
+                .ok_millis,
+            70 * 60_000
+        );
+        assert_eq!(db.get_history_bounds("a").await?, Some(at(0)..at(24 * 60)));
+
+        db.delete_before(at(24 * 60)).await?;
+        assert!(db
+            .get_checks_aggregates_in_range("a", at(0)..at(24 * 60))
+            .await?
+            .is_empty());
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn get_bins_counts_overlapping_series() {
+    with_each_backend("bins", |db| async move {
+        website_a(&*db).await?;
+        db.merge_series(&[
+            series(CheckState::Ok, 10..50),
+            series(CheckState::NotOk, 70..140),
+            series(CheckState::Paused, 190..200),
+        ])
+        .await?;
+
+        let bins = db
+            .get_bins("a", at(0)..at(4 * 60), 4)
+            .await?
+            .into_iter()
+            .map(|bin| (bin.ok_series, bin.not_ok_series, bin.paused_series))
+            .collect::<Vec<_>>();
+        assert_eq!(bins, [(1, 0, 0), (0, 1, 0), (0, 1, 0), (0, 0, 1)]);
+
+        // Aggregates count once for every bin they overlap with.
+        db.rollup_series(at(4 * 60)).await?;
+        db.rollup_aggregates(Resolution::Hour, Resolution::Day, at(4 * 60))
+            .await?;
+        let bins = db
+            .get_bins("a", at(6 * 60)..at(12 * 60), 2)
+            .await?
+            .into_iter()
+            .map(|bin| (bin.ok_series, bin.not_ok_series, bin.paused_series))
+            .collect::<Vec<_>>();
+        assert_eq!(bins, [(1, 2, 1), (1, 2, 1)]);
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn get_state_millis_counts_overlapping_parts() {
+    with_each_backend("state_millis", |db| async move {
+        website_a(&*db).await?;
+        db.merge_series(&[
+            series(CheckState::Ok, 10..50),
+            series(CheckState::Paused, 60..90),
+            series(CheckState::NotOk, 90..100),
+            series(CheckState::Ok, 100..130),
+        ])
+        .await?;
+
+        let minutes =
+            |millis: StateMillis| (millis.ok_millis / 60_000, millis.paused_millis / 60_000);
+
+        // Series count with the part inside the range.
+        let millis = db.get_state_millis("a", at(20)..at(110)).await?;
+        assert_eq!(minutes(millis), (40, 30));
+        let millis = db.get_state_millis("a", at(200)..at(300)).await?;
+        assert_eq!(minutes(millis), (0, 0));
+
+        // Aggregates count with the share of their bucket inside the range.
+        db.rollup_series(at(24 * 60)).await?;
+        let millis = db.get_state_millis("a", at(30)..at(90)).await?;
+        assert_eq!(minutes(millis), (30, 15));
+        let millis = db.get_state_millis("a", at(0)..at(24 * 60)).await?;
+        assert_eq!(minutes(millis), (70, 30));
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn anonymous_export_leaves_out_hidden_websites() {
+    with_each_backend("export_visibility", |db| async move {
+        let mut config = vec![
+            website(serde_json::json!({ "name": "a", "url": "https://a.example.com" })),
+            website(serde_json::json!({
+                "name": "secret",
+                "url": "https://secret.example.com",
+                "public": false,
+            })),
+        ];
+        db.sync_websites(&mut config).await?;
+        let in_website = |website: &str| CheckSeries {
+            website: website.into(),
+            ..series(CheckState::Ok, 0..10)
+        };
+        db.merge_series(&[in_website("a"), in_website("secret"), in_website("removed")])
+            .await?;
+
+        let export = |websites: &[&str], private| {
+            let db = db.clone();
+            let websites = websites.iter().map(|&slug| slug.to_owned()).collect();
+            async move { crate::export::export_websites(&*db, websites, private).await }
+        };
+        assert_eq!(export(&[], false).await?, ["a"]);
+        assert!(export(&["secret"], false).await?.is_empty());
+        // Removed websites might have been private.
+        assert!(export(&["removed"], false).await?.is_empty());
+        assert_eq!(export(&["", "a", ""], false).await?, ["a"]);
+
+        assert_eq!(export(&[], true).await?, ["a", "secret"]);
+        assert_eq!(
+            export(&["secret", "removed"], true).await?,
+            ["secret", "removed"]
+        );
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn export_and_import_round_trip() {
+    use crate::export::{export, import, ExportFormat};
+
+    /// Everything about a series that is exported.
+    fn exported(series: Vec<CheckSeries>) -> Vec<String> {
+        series
+            .into_iter()
+            .map(|series| format!("{:?}", crate::export::ExportRow::from(series)))
+            .collect()
+    }
+
+    with_each_backend("export_import", |db| async move {
+        website_a(&*db).await?;
+        let mut with_latency = series(CheckState::NotOk, 30..40);
+        with_latency.latency.add_sample(120);
+        with_latency.latency.add_sample(80);
+        db.merge_series(&[series(CheckState::Ok, 10..30), with_latency])
+            .await?;
+        let all = at(0)..at(24 * 60);
+        let original = exported(db.get_checks_series_in_range("a", all.clone()).await?);
+
+        for format in [ExportFormat::Jsonl, ExportFormat::Csv] {
+            let mut file = Vec::new();
+            export(&*db, Vec::new(), all.clone(), format, &mut file).await?;
+
+            db.delete_before(at(24 * 60)).await?;
+            assert!(db
+                .get_checks_series_in_range("a", all.clone())
+                .await?
+                .is_empty());
+
+            assert_eq!(import(&*db, format, file.as_slice()).await?, 2);
+            let imported = exported(db.get_checks_series_in_range("a", all.clone()).await?);
+            assert_eq!(imported, original, "{format:?}");
+
+            // Importing the same file again doesn't duplicate anything.
+            assert_eq!(import(&*db, format, file.as_slice()).await?, 0);
+            assert_eq!(
+                db.get_checks_series_in_range("a", all.clone()).await?.len(),
+                2
+            );
+        }
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn get_incidents_filters_resolved_and_orders_by_latest_update() {
+    fn update(status: IncidentStatus, minutes: i64) -> IncidentUpdate {
+        IncidentUpdate {
+            status,
+            message: format!("{} at {minutes}", status.label()),
+            time: at(minutes),
+        }
+    }
+
+    /// The titles, statuses and update counts of the incidents updated after `since`.
+    async fn incidents(
+        db: &dyn Storage,
+        since: i64,
+    ) -> Result<Vec<(String, IncidentStatus, usize)>> {
+        Ok(db
+            .get_incidents(at(since))
+            .await?
+            .into_iter()
+            .map(|incident| {
+                (
+                    incident.title.clone(),
+                    incident.status(),
+                    incident.updates.len(),
+                )
+            })
+            .collect())
+    }
+
+    with_each_backend("incidents", |db| async move {
+        let mut ids = Vec::new();
+        for (title, minutes) in [("a", 0), ("b", 10), ("c", 20)] {
+            let incident = NewIncident {
+                title: title.into(),
+                severity: Severity::Major,
+                websites: vec!["a".into()],
+                update: update(IncidentStatus::Investigating, minutes),
+            };
+            ids.push(db.create_incident(&incident).await?);
+        }
+
+        assert!(
+            db.add_incident_update(ids[0], &update(IncidentStatus::Resolved, 30))
+                .await?
+        );
+        assert!(
+            db.add_incident_update(ids[1], &update(IncidentStatus::Monitoring, 40))
+                .await?
+        );
+        assert!(
+            !db.add_incident_update(ids[2] + 1, &update(IncidentStatus::Resolved, 40))
+                .await?
+        );
+
+        use IncidentStatus::*;
+        assert_eq!(
+            incidents(&*db, 25).await?,
+            [
+                ("b".into(), Monitoring, 2),
+                ("a".into(), Resolved, 2),
+                ("c".into(), Investigating, 1)
+            ]
+        );
+        // Resolved incidents are only returned if they were resolved after `since`.
+        assert_eq!(
+            incidents(&*db, 30).await?,
+            [("b".into(), Monitoring, 2), ("c".into(), Investigating, 1)]
+        );
+
+        // Incidents updated at the same time are ordered by their id, newest first.
+        assert!(
+            db.add_incident_update(ids[2], &update(IncidentStatus::Resolved, 40))
+                .await?
+        );
+        assert_eq!(
+            incidents(&*db, 30).await?,
+            [("c".into(), Resolved, 2), ("b".into(), Monitoring, 2)]
+        );
+
+        let a = db.get_incident(ids[0]).await?.unwrap();
+        let timeline = a
+            .updates
+            .iter()
+            .map(|update| (update.status, update.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            timeline,
+            [
+                (Investigating, "Investigating at 0"),
+                (Resolved, "Resolved at 30")
+            ]
+        );
+        assert_eq!(a.created_at, at(0));
+        assert!(db.get_incident(ids[2] + 1).await?.is_none());
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn outages_follow_state_changes() {
+    with_each_backend("outages", |db| async move {
+        website_a(&*db).await?;
+        db.merge_series(&[
+            series(CheckState::Ok, 0..10),
+            series(CheckState::NotOk, 10..20),
+            series(CheckState::NotOk, 30..40),
+            series(CheckState::Ok, 40..50),
+            series(CheckState::NotOk, 60..70),
+            series(CheckState::Paused, 70..80),
+            series(CheckState::NotOk, 90..100),
+        ])
+        .await?;
+
+        let website = db.get_websites().await?.remove(0);
+        let outages = crate::outage::outages(&*db, &website, at(0)..at(24 * 60))
+            .await?
+            .into_iter()
+            .map(|outage| {
+                (
+                    outage.website,
+                    (outage.start - at(0)).num_minutes(),
+                    outage.end.map(|end| (end - at(0)).num_minutes()),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outages,
+            [
+                // Consecutive failed series are one outage, even with a gap between them.
+                ("a".into(), 10, Some(40)),
+                // Pausing ends an outage like an OK check.
+                ("a".into(), 60, Some(70)),
+                // The website is still down at the end of the range.
+                ("a".into(), 90, None)
+            ]
+        );
+        Ok(())
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn feed_has_outages_incidents_and_announcements() {
+    with_each_backend("feed", |db| async move {
+        let config = serde_json::from_value::<crate::config::Config>(serde_json::json!({
+            "interval_seconds": 30,
+            "websites": [
+                { "name": "a", "url": "https://a.example.com" },
+                { "name": "hidden", "url": "https://hidden.example.com", "public": false },
+            ],
+            "announcements": [
+                { "title": "Maintenance", "time": at(60) },
+            ],
+        }))?;
+        let mut websites = config.websites.clone();
+        db.sync_websites(&mut websites).await?;
+
+        let mut hidden = series(CheckState::NotOk, 15..25);
+        hidden.website = "hidden".into();
+        db.merge_series(&[
+            series(CheckState::NotOk, 10..20),
+            series(CheckState::Ok, 20..30),
+            hidden,
+        ])
+        .await?;
+
+        for (title, website) in [("Database trouble", "a"), ("Secret trouble", "hidden")] {
+            let incident = NewIncident {
+                title: title.into(),
+                severity: Severity::Minor,
+                websites: vec![website.into()],
+                update: IncidentUpdate {
+                    status: IncidentStatus::Investigating,
+                    message: "Looking into it".into(),
+                    time: at(40),
+                },
+            };
+            let id = db.create_incident(&incident).await?;
+            let update = IncidentUpdate {
+                status: IncidentStatus::Monitoring,
+                message: "Fixed, watching".into(),
+                time: at(50),
+            };
+            db.add_incident_update(id, &update).await?;
+        }
+
+        let feed = crate::feed::render(
+            &*db,
+            &config,
+            None,
+            crate::feed::FeedFormat::Atom,
+            false,
+            "https://status.example.com/",
+        )
+        .await?;
+
+        let titles = feed
+            .split("<entry>")
+            .skip(1)
+            .map(|entry| {
+                let start = entry.find("<title>").unwrap() + "<title>".len();
+                let end = entry.find("</title>").unwrap();
+                &entry[start..end]
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            titles,
+            [
+                "Maintenance",
+                "Database trouble: Monitoring",
+                "Database trouble: Investigating",
+                "a was down for 10m"
+            ]
+        );
+        assert!(!feed.contains("hidden"), "{feed}");
+        assert!(!feed.contains("Secret"), "{feed}");
+        Ok(())
+    })
+    .await;
+}
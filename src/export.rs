@@ -0,0 +1,230 @@
+use std::{
+    io::{BufRead, Write},
+    ops::Range,
+};
+
+use chrono::{DateTime, Utc};
+use eyre::{Context, Result};
+use futures_util::{Stream, StreamExt};
+
+use crate::{
+    client::CheckState,
+    db::{CheckSeries, LatencySummary, Storage},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// One JSON object per line.
+    Jsonl,
+    /// Comma separated values with a header.
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Jsonl => "application/jsonl",
+            Self::Csv => "text/csv",
+        }
+    }
+}
+
+/// A single series in the export format, independent of the database.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ExportRow {
+    pub website: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub result: CheckState,
+    pub latency_count: i64,
+    pub latency_sum_ms: i64,
+    pub latency_min_ms: Option<i64>,
+    pub latency_max_ms: Option<i64>,
+}
+
+impl From<CheckSeries> for ExportRow {
+    fn from(series: CheckSeries) -> Self {
+        Self {
+            website: series.website,
+            start: series.request_time_range_start,
+            end: series.request_time_range_end,
+            result: series.result,
+            latency_count: series.latency.count,
+            latency_sum_ms: series.latency.sum_ms,
+            latency_min_ms: series.latency.min_ms,
+            latency_max_ms: series.latency.max_ms,
+        }
+    }
+}
+
+impl From<ExportRow> for CheckSeries {
+    fn from(row: ExportRow) -> Self {
+        Self {
+            id: 0,
+            request_time_range_start: row.start,
+            request_time_range_end: row.end,
+            website: row.website,
+            result: row.result,
+            latency: LatencySummary {
+                count: row.latency_count,
+                sum_ms: row.latency_sum_ms,
+                min_ms: row.latency_min_ms,
+                max_ms: row.latency_max_ms,
+            },
+        }
+    }
+}
+
+/// The websites to export, all configured websites if none are given explicitly. Without
+/// `private`, only public websites that are currently configured or managed are exported, since the
+/// history of removed websites doesn't say whether they were public.
+pub async fn export_websites<S: Storage + ?Sized>(
+    db: &S,
+    websites: Vec<String>,
+    private: bool,
+) -> Result<Vec<String>> {
+    let stored = db.get_websites().await?;
+    let visible = |slug: &str| {
+        private
+            || stored
+                .iter()
+                .any(|website| website.slug == slug && website.public)
+    };
+
+    let websites = websites
+        .into_iter()
+        .filter(|slug| !slug.is_empty())
+        .collect::<Vec<_>>();
+    if !websites.is_empty() {
+        return Ok(websites.into_iter().filter(|slug| visible(slug)).collect());
+    }
+    Ok(stored
+        .iter()
+        .filter(|website| visible(&website.slug))
+        .map(|website| website.slug.clone())
+        .collect())
+}
+
+/// Encodes a single row, followed by a newline.
+fn encode_row(format: ExportFormat, row: &ExportRow) -> Result<Vec<u8>> {
+    match format {
+        ExportFormat::Jsonl => {
+            let mut out = serde_json::to_vec(row).wrap_err("encoding series")?;
+            out.push(b'\n');
+            Ok(out)
+        }
+        ExportFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(Vec::new());
+            writer.serialize(row).wrap_err("encoding series")?;
+            writer.into_inner().wrap_err("encoding series")
+        }
+    }
+}
+
+/// The CSV header, written by hand so it's there even without any rows.
+fn encode_header() -> Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(EXPORT_HEADER)
+        .wrap_err("encoding header")?;
+    writer.into_inner().wrap_err("encoding header")
+}
+
+const EXPORT_HEADER: [&str; 8] = [
+    "website",
+    "start",
+    "end",
+    "result",
+    "latency_count",
+    "latency_sum_ms",
+    "latency_min_ms",
+    "latency_max_ms",
+];
+
+/// Encodes all series of `websites` in `range` as they are fetched, one website at a time and
+/// one chunk per series.
+pub fn export_stream<'a, S: Storage + ?Sized>(
+    db: &'a S,
+    websites: &'a [String],
+    range: Range<DateTime<Utc>>,
+    format: ExportFormat,
+) -> impl Stream<Item = Result<Vec<u8>>> + Send + 'a {
+    let header = (format == ExportFormat::Csv).then(encode_header);
+
+    futures_util::stream::iter(header).chain(
+        futures_util::stream::iter(websites)
+            .flat_map(move |website| db.stream_checks_series_in_range(website, range.clone()))
+            .map(move |series| encode_row(format, &ExportRow::from(series?))),
+    )
+}
+
+/// Writes all series of `websites` in `range` to `out`.
+pub async fn export<S: Storage + ?Sized>(
+    db: &S,
+    websites: Vec<String>,
+    range: Range<DateTime<Utc>>,
+    format: ExportFormat,
+    mut out: impl Write,
+) -> Result<()> {
+    let websites = export_websites(db, websites, true).await?;
+
+    let mut chunks = std::pin::pin!(export_stream(db, &websites, range, format));
+    while let Some(chunk) = chunks.next().await {
+        out.write_all(&chunk?).wrap_err("writing export")?;
+    }
+
+    out.flush().wrap_err("writing export")
+}
+
+/// How many series are merged into the database at once while importing.
+pub(crate) const IMPORT_BATCH_SIZE: usize = 1000;
+
+/// Reads series from `input` row by row and merges them into the database in batches, returning
+/// the number of series that were not present yet.
+pub async fn import<S: Storage + ?Sized>(
+    db: &S,
+    format: ExportFormat,
+    input: impl BufRead,
+) -> Result<usize> {
+    match format {
+        ExportFormat::Jsonl => {
+            let rows = input
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+                .map(|(i, line)| {
+                    let line = line.wrap_err("reading input")?;
+                    serde_json::from_str::<ExportRow>(&line)
+                        .wrap_err_with(|| format!("invalid series on line {}", i + 1))
+                });
+            merge_rows(db, rows).await
+        }
+        ExportFormat::Csv => {
+            let rows = csv::Reader::from_reader(input)
+                .into_deserialize::<ExportRow>()
+                .enumerate()
+                .map(|(i, row)| row.wrap_err_with(|| format!("invalid series in row {}", i + 1)));
+            merge_rows(db, rows).await
+        }
+    }
+}
+
+async fn merge_rows<S: Storage + ?Sized>(
+    db: &S,
+    rows: impl Iterator<Item = Result<ExportRow>>,
+) -> Result<usize> {
+    let mut inserted = 0;
+    let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    for row in rows {
+        batch.push(CheckSeries::from(row?));
+        if batch.len() == IMPORT_BATCH_SIZE {
+            inserted += db.merge_series(&batch).await?;
+            batch.clear();
+        }
+    }
+
+    Ok(inserted + db.merge_series(&batch).await?)
+}
@@ -0,0 +1,242 @@
+//! Atom and RSS feeds of outages, incidents and announcements, to get notified in a feed reader.
+
+use askama::Template;
+use chrono::{DateTime, Utc};
+use eyre::{Context, Result};
+
+use crate::{
+    config::Config,
+    db::{Storage, Website},
+    outage::{self, Outage},
+    web::{self, path_segment, OUTAGE_DAYS},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+impl FeedFormat {
+    pub(crate) const ALL: [FeedFormat; 2] = [FeedFormat::Atom, FeedFormat::Rss];
+
+    pub(crate) fn file_name(self) -> &'static str {
+        match self {
+            Self::Atom => "feed.atom",
+            Self::Rss => "feed.rss",
+        }
+    }
+
+    pub(crate) fn from_file_name(file_name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.file_name() == file_name)
+    }
+
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            Self::Atom => "application/atom+xml",
+            Self::Rss => "application/rss+xml",
+        }
+    }
+}
+
+/// An outage or an announcement.
+struct Entry {
+    id: String,
+    title: String,
+    summary: String,
+    link: String,
+    published: DateTime<Utc>,
+    /// Outages are updated when they end.
+    updated: DateTime<Utc>,
+}
+
+impl Entry {
+    fn outage(outage: &Outage, base_url: &str) -> Self {
+        let (title, summary) = match outage.end {
+            Some(end) => (
+                format!("{} was down for {}", outage.name, outage.duration()),
+                format!(
+                    "{} was down from {} until {}.",
+                    outage.name,
+                    outage.start.to_rfc3339(),
+                    end.to_rfc3339()
+                ),
+            ),
+            None => (
+                format!("{} is down", outage.name),
+                format!(
+                    "{} has been down since {}.",
+                    outage.name,
+                    outage.start.to_rfc3339()
+                ),
+            ),
+        };
+        let segment = path_segment(&outage.website);
+
+        Self {
+            id: format!(
+                "urn:uptime:outage:{}:{}",
+                urlencode(&segment),
+                outage.start.timestamp_millis()
+            ),
+            title,
+            summary,
+            link: format!("{base_url}websites/{}/", urlencode(&segment)),
+            published: outage.start,
+            updated: outage.updated(),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "feed.atom", escape = "html")]
+struct AtomTemplate<'a> {
+    title: &'a str,
+    id: &'a str,
+    link: &'a str,
+    updated: DateTime<Utc>,
+    entries: &'a [Entry],
+    version: &'static str,
+}
+
+#[derive(Template)]
+#[template(path = "feed.rss", escape = "html")]
+struct RssTemplate<'a> {
+    title: &'a str,
+    link: &'a str,
+    entries: &'a [Entry],
+    version: &'static str,
+}
+
+/// Renders the feed of the outages and incidents of the last `OUTAGE_DAYS` and the announcements of
+/// `website`, or of all websites, with private websites if `private` is set. Links point to pages
+/// below `base_url`, which ends with a slash.
+pub(crate) async fn render<S: Storage + ?Sized>(
+    db: &S,
+    config: &Config,
+    website: Option<&Website>,
+    format: FeedFormat,
+    private: bool,
+    base_url: &str,
+) -> Result<String> {
+    let visible = web::visible_websites(db, private).await?;
+    let websites = match website {
+        Some(website) => vec![website.clone()],
+        None => visible.clone(),
+    };
+
+    let (title, id, link) = match website {
+        Some(website) => {
+            let segment = urlencode(&path_segment(&website.slug));
+            (
+                format!("{} status", website.name),
+                format!("urn:uptime:feed:{segment}"),
+                format!("{base_url}websites/{segment}/"),
+            )
+        }
+        None => (
+            "Status".to_owned(),
+            "urn:uptime:feed".to_owned(),
+            base_url.to_owned(),
+        ),
+    };
+
+    let mut entries = Vec::new();
+    for website in &websites {
+        let Some(bounds) = db.get_history_bounds(&website.slug).await? else {
+            continue;
+        };
+        let range = web::clamp_range(bounds, chrono::Duration::days(OUTAGE_DAYS));
+        for outage in outage::outages(db, website, range).await? {
+            entries.push(Entry::outage(&outage, base_url));
+        }
+    }
+
+    for incident in web::recent_incidents(db, private).await? {
+        if let Some(website) = website {
+            if !incident.websites.contains(&website.slug) {
+                continue;
+            }
+        }
+        // Every update is its own entry, so that readers notice them.
+        for (i, update) in incident.updates.iter().enumerate() {
+            entries.push(Entry {
+                id: format!("urn:uptime:incident:{}:{i}", incident.id),
+                title: format!("{}: {}", incident.title, update.status.label()),
+                summary: update.message.clone(),
+                link: link.clone(),
+                published: update.time,
+                updated: update.time,
+            });
+        }
+    }
+
+    for announcement in &config.announcements {
+        let concerns_website = match website {
+            Some(website) => {
+                announcement.websites.is_empty() || announcement.websites.contains(&website.name)
+            }
+            None => {
+                announcement.websites.is_empty()
+                    || visible
+                        .iter()
+                        .any(|website| announcement.websites.contains(&website.name))
+            }
+        };
+        if concerns_website {
+            entries.push(Entry {
+                id: format!(
+                    "urn:uptime:announcement:{}:{}",
+                    announcement.time.timestamp_millis(),
+                    urlencode(&announcement.title)
+                ),
+                title: announcement.title.clone(),
+                summary: announcement.message.clone().unwrap_or_default(),
+                link: base_url.to_owned(),
+                published: announcement.time,
+                updated: announcement.time,
+            });
+        }
+    }
+
+    entries.sort_by(|a, b| b.updated.cmp(&a.updated));
+
+    match format {
+        FeedFormat::Atom => AtomTemplate {
+            title: &title,
+            id: &id,
+            link: &link,
+            updated: entries
+                .iter()
+                .map(|entry| entry.updated)
+                .max()
+                .unwrap_or_else(Utc::now),
+            entries: &entries,
+            version: crate::VERSION,
+        }
+        .render(),
+        FeedFormat::Rss => RssTemplate {
+            title: &title,
+            link: &link,
+            entries: &entries,
+            version: crate::VERSION,
+        }
+        .render(),
+    }
+    .wrap_err("rendering feed")
+}
+
+/// The URL links in feeds are relative to, `public_url` if configured and `relative` otherwise.
+pub(crate) fn base_url(config: &Config, relative: &str) -> String {
+    match &config.public_url {
+        Some(url) if url.as_str().ends_with('/') => url.to_string(),
+        Some(url) => format!("{url}/"),
+        None => relative.to_owned(),
+    }
+}
+
+fn urlencode(text: &str) -> String {
+    askama::filters::urlencode_strict(text).expect("urlencoding never fails")
+}
@@ -0,0 +1,454 @@
+//! Importers for the history of other uptime monitoring tools.
+
+use std::{collections::HashMap, io::Read, path::Path, str::FromStr};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use eyre::{bail, Context, ContextCompat, Result};
+use futures_util::TryStreamExt;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteRow},
+    Pool, Row, Sqlite,
+};
+use url::Url;
+
+use crate::{
+    client::{CheckResult, CheckState},
+    config::WebsiteConfig,
+    db::{insert_single_result_series_in_memory, CheckSeries, LatencySummary, Storage},
+    export::IMPORT_BATCH_SIZE,
+};
+
+/// The outcome of an import.
+pub struct Imported {
+    /// A config entry for every imported monitor that has a URL.
+    pub websites: Vec<WebsiteConfig>,
+    /// The amount of series that were not present in the database yet.
+    pub inserted: usize,
+}
+
+/// Imports the heartbeats of all monitors from the SQLite database of Uptime Kuma.
+/// The history of every monitor is stored under its name.
+pub async fn import_uptime_kuma<S: Storage + ?Sized>(db: &S, kuma_db: &Path) -> Result<Imported> {
+    let opts = SqliteConnectOptions::new()
+        .filename(kuma_db)
+        .read_only(true);
+    let kuma: Pool<Sqlite> = Pool::connect_with(opts)
+        .await
+        .wrap_err_with(|| format!("opening Uptime Kuma db at {}", kuma_db.display()))?;
+
+    let monitors = sqlx::query("SELECT id, name, url, interval FROM monitor ORDER BY id")
+        .try_map(|row: SqliteRow| {
+            Ok((
+                row.try_get::<i64, _>("id")?,
+                row.try_get::<String, _>("name")?,
+                row.try_get::<Option<String>, _>("url")?,
+                row.try_get::<Option<i64>, _>("interval")?,
+            ))
+        })
+        .fetch_all(&kuma)
+        .await
+        .wrap_err("getting monitors")?;
+
+    let mut websites = Vec::new();
+    let mut inserted = 0;
+    for (id, name, url, interval) in monitors {
+        // Kuma stores times as `YYYY-MM-DD HH:MM:SS.SSS` in UTC.
+        let mut heartbeats = sqlx::query(
+            "SELECT status, CAST(time AS TEXT) AS time, ping FROM heartbeat
+            WHERE monitor_id = ?
+            ORDER BY time ASC",
+        )
+        .bind(id)
+        .try_map(|row: SqliteRow| {
+            Ok((
+                row.try_get::<i64, _>("status")?,
+                row.try_get::<String, _>("time")?,
+                row.try_get::<Option<i64>, _>("ping")?,
+            ))
+        })
+        .fetch(&kuma);
+
+        let interval_seconds = interval.unwrap_or(60).max(1) as u64;
+
+        let mut series = Vec::new();
+        let mut latest_cache = HashMap::new();
+        let mut imported = 0;
+        while let Some((status, time, ping)) = heartbeats
+            .try_next()
+            .await
+            .wrap_err_with(|| format!("getting heartbeats of {name}"))?
+        {
+            let state = match status {
+                1 => CheckState::Ok,
+                // Pending heartbeats are failed checks that are retried.
+                0 | 2 => CheckState::NotOk,
+                // Maintenance, which says nothing about the website.
+                _ => continue,
+            };
+            let check = CheckResult {
+                time: parse_naive_utc(&time)?,
+                state,
+                latency_ms: ping,
+                reason: None,
+            };
+            insert_single_result_series_in_memory(
+                &mut series,
+                &mut latest_cache,
+                interval_seconds,
+                &name,
+                &check,
+            )?;
+
+            // The latest series may still be continued by the next heartbeat, so it stays behind.
+            if series.len() > IMPORT_BATCH_SIZE {
+                let latest = series.pop().expect("there is a latest series");
+                inserted += db.merge_series(&series).await?;
+                imported += series.len();
+                series.clear();
+                series.push(latest);
+                latest_cache.insert(name.clone(), 0);
+            }
+        }
+
+        inserted += db.merge_series(&series).await?;
+        imported += series.len();
+        info!(%name, series = imported, "Imported monitor");
+
+        match url.as_deref().map(Url::from_str) {
+            Some(Ok(url)) => websites.push(website_config(name, url)),
+            _ => warn!(%name, "Monitor has no HTTP URL, not generating a config entry"),
+        }
+    }
+
+    Ok(Imported { websites, inserted })
+}
+
+/// Imports the event log CSV exported from UptimeRobot.
+///
+/// The log only contains changes of the state, so every event turns into a series lasting until
+/// the next event of its monitor. The last event lasts for its duration if the log contains it, and
+/// until the last event in the whole log otherwise. Events that would end right away are skipped.
+pub async fn import_uptime_robot<S: Storage + ?Sized>(
+    db: &S,
+    input: impl Read,
+) -> Result<Imported> {
+    let mut reader = csv::Reader::from_reader(input);
+
+    let headers = reader.headers().wrap_err("reading header")?.clone();
+    let column = |names: &[&str]| {
+        headers
+            .iter()
+            .position(|header| names.contains(&header.trim().to_lowercase().as_str()))
+    };
+    let name_column = column(&["monitor", "monitor name", "friendly name", "name"])
+        .wrap_err("log has no monitor column")?;
+    let time_column =
+        column(&["date-time", "datetime", "date"]).wrap_err("log has no date column")?;
+    let event_column = column(&["event", "type"]).wrap_err("log has no event column")?;
+    let url_column = column(&["monitor url", "url"]);
+    let duration_column = column(&["duration (in mins.)", "duration (mins)"]);
+
+    struct Event {
+        time: DateTime<Utc>,
+        state: Option<CheckState>,
+        duration_mins: Option<i64>,
+    }
+
+    let mut monitors = HashMap::<String, (Option<Url>, Vec<Event>)>::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record.wrap_err_with(|| format!("reading row {}", i + 1))?;
+        let field = |column: usize| record.get(column).unwrap_or_default().trim();
+
+        let state = match field(event_column).to_lowercase().as_str() {
+            "up" => Some(CheckState::Ok),
+            "down" => Some(CheckState::NotOk),
+            // Paused, started and other events end the current series without starting a new one.
+            _ => None,
+        };
+        let event = Event {
+            time: parse_naive_utc(field(time_column))
+                .wrap_err_with(|| format!("invalid date in row {}", i + 1))?,
+            state,
+            duration_mins: duration_column.and_then(|column| field(column).parse().ok()),
+        };
+
+        let (url, events) = monitors.entry(field(name_column).to_owned()).or_default();
+        if url.is_none() {
+            *url = url_column.and_then(|column| Url::from_str(field(column)).ok());
+        }
+        events.push(event);
+    }
+
+    let log_end = monitors
+        .values()
+        .flat_map(|(_, events)| events.iter().map(|event| event.time))
+        .max();
+    let mut monitors = monitors.into_iter().collect::<Vec<_>>();
+    monitors.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut websites = Vec::new();
+    let mut inserted = 0;
+    for (name, (url, mut events)) in monitors {
+        events.sort_by_key(|event| event.time);
+
+        let series = events
+            .iter()
+            .enumerate()
+            .filter_map(|(i, event)| {
+                let state = event.state?;
+                let end = match (events.get(i + 1), event.duration_mins) {
+                    (Some(next), _) => next.time,
+                    (None, Some(duration_mins)) => {
+                        event.time + chrono::Duration::minutes(duration_mins)
+                    }
+                    (None, None) => log_end?,
+                };
+                // An empty series would look like a check that never happened.
+                if end <= event.time {
+                    return None;
+                }
+                Some(CheckSeries {
+                    id: 0,
+                    request_time_range_start: event.time,
+                    request_time_range_end: end,
+                    website: name.clone(),
+                    result: state,
+                    latency: LatencySummary::default(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        inserted += db.merge_series(&series).await?;
+        info!(%name, series = series.len(), "Imported monitor");
+
+        match url {
+            Some(url) => websites.push(website_config(name, url)),
+            None => warn!(%name, "Monitor has no URL in the log, not generating a config entry"),
+        }
+    }
+
+    Ok(Imported { websites, inserted })
+}
+
+fn website_config(name: String, url: Url) -> WebsiteConfig {
+    WebsiteConfig {
+        name,
+        url,
+        slug: None,
+        previous_names: Vec::new(),
+        archived: false,
+        paused: false,
+        group: None,
+        tags: Vec::new(),
+        public: true,
+    }
+}
+
+/// Parses RFC 3339 timestamps or timestamps without a time zone, which are assumed to be UTC.
+fn parse_naive_utc(time: &str) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    for format in [
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%m/%d/%Y %H:%M:%S",
+    ] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(time, format) {
+            return Ok(time.and_utc());
+        }
+    }
+    bail!("invalid timestamp `{time}`")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use crate::{
+        client::CheckState,
+        db::{MemoryStorage, Storage},
+    };
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, hour, minute, 0).unwrap()
+    }
+
+    #[tokio::test]
+    async fn uptime_kuma_heartbeats() {
+        let path = std::env::temp_dir().join(format!("uptime-kuma-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let opts = sqlx::sqlite::SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true);
+        let kuma = sqlx::SqlitePool::connect_with(opts).await.unwrap();
+        sqlx::query(
+            "CREATE TABLE monitor (id INTEGER PRIMARY KEY, name VARCHAR, url VARCHAR, interval INTEGER);
+            CREATE TABLE heartbeat (
+                id INTEGER PRIMARY KEY, monitor_id INTEGER, status INTEGER, time DATETIME, ping INTEGER
+            );
+            INSERT INTO monitor VALUES
+                (1, 'api', 'https://api.example.com', 60),
+                (2, 'cron', NULL, 60),
+                (3, 'flaky', 'https://flaky.example.com', 60);
+            INSERT INTO heartbeat (monitor_id, status, time, ping) VALUES
+                (1, 1, '2024-01-01 10:00:00.000', 100),
+                (1, 1, '2024-01-01 10:01:00.000', 120),
+                (1, 0, '2024-01-01 10:02:00.000', NULL),
+                (1, 2, '2024-01-01 10:03:00.000', NULL),
+                (1, 1, '2024-01-01 10:04:00.000', 80),
+                (1, 3, '2024-01-01 10:05:00.000', NULL),
+                (1, 1, '2024-01-01 10:06:00.000', 90),
+                (1, 1, '2024-01-01 10:20:00.000', 110),
+                (2, 0, '2024-01-01 10:00:00.500', NULL),
+                (2, 0, '2024-01-01 10:01:00.500', NULL);",
+        )
+        .execute(&kuma)
+        .await
+        .unwrap();
+        // Every heartbeat starts a new series, so they are merged in more than one batch.
+        let flaky = 2 * super::IMPORT_BATCH_SIZE + 500;
+        for i in 0..flaky {
+            sqlx::query("INSERT INTO heartbeat (monitor_id, status, time) VALUES (3, ?, ?)")
+                .bind((i % 2) as i64)
+                .bind(
+                    (at(0, 0) + chrono::Duration::seconds(30 * i as i64))
+                        .format("%Y-%m-%d %H:%M:%S%.3f")
+                        .to_string(),
+                )
+                .execute(&kuma)
+                .await
+                .unwrap();
+        }
+        kuma.close().await;
+
+        let db = MemoryStorage::new();
+        let imported = super::import_uptime_kuma(&db, &path).await.unwrap();
+        assert_eq!(imported.inserted, 5 + flaky);
+
+        let websites = imported
+            .websites
+            .iter()
+            .map(|website| {
+                (
+                    website.name.as_str(),
+                    website.url.as_str(),
+                    website.slug.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            websites,
+            [
+                ("api", "https://api.example.com/", None),
+                ("flaky", "https://flaky.example.com/", None)
+            ]
+        );
+
+        let series = |website| {
+            let db = &db;
+            async move {
+                db.get_checks_series_in_range(website, at(0, 0)..at(23, 0))
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|series| {
+                        (
+                            series.result,
+                            series.request_time_range_start,
+                            series.request_time_range_end,
+                            series.latency.count,
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            }
+        };
+        // Pending heartbeats count as down, maintenance doesn't interrupt the series and a gap of
+        // more than five intervals starts a new one.
+        assert_eq!(
+            series("api").await,
+            [
+                (CheckState::Ok, at(10, 0), at(10, 1), 2),
+                (CheckState::NotOk, at(10, 2), at(10, 3), 0),
+                (CheckState::Ok, at(10, 4), at(10, 6), 2),
+                (CheckState::Ok, at(10, 20), at(10, 20), 1)
+            ]
+        );
+        let half_second = chrono::Duration::milliseconds(500);
+        assert_eq!(
+            series("cron").await,
+            [(
+                CheckState::NotOk,
+                at(10, 0) + half_second,
+                at(10, 1) + half_second,
+                0
+            )]
+        );
+        assert_eq!(series("flaky").await.len(), flaky);
+
+        // The series are already there.
+        let imported = super::import_uptime_kuma(&db, &path).await.unwrap();
+        assert_eq!(imported.inserted, 0);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn uptime_robot_events_out_of_order() {
+        let log = "\
+Date-Time,Monitor,Monitor URL,Event,Duration (in mins.)
+2024-01-01 10:30:00,api,https://api.example.com,Up,
+2024-01-01 10:00:00,api,https://api.example.com,Down,30
+2024-01-01 11:00:00,web,https://web.example.com,Up,
+2024-01-01 10:45:00,web,https://web.example.com,Down,15
+2024-01-01 10:50:00,cron,,Down,5
+";
+        let db = MemoryStorage::new();
+        let imported = super::import_uptime_robot(&db, log.as_bytes())
+            .await
+            .unwrap();
+
+        let names = imported
+            .websites
+            .iter()
+            .map(|website| website.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["api", "web"]);
+        assert_eq!(imported.inserted, 4);
+
+        let series = |website| {
+            let db = &db;
+            async move {
+                db.get_checks_series_in_range(website, at(0, 0)..at(23, 0))
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|series| {
+                        (
+                            series.result,
+                            series.request_time_range_start,
+                            series.request_time_range_end,
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            }
+        };
+        // Without a duration, the last event lasts until the end of the log.
+        assert_eq!(
+            series("api").await,
+            [
+                (CheckState::NotOk, at(10, 0), at(10, 30)),
+                (CheckState::Ok, at(10, 30), at(11, 0))
+            ]
+        );
+        // It's the end of the log, so it would be empty.
+        assert_eq!(
+            series("web").await,
+            [(CheckState::NotOk, at(10, 45), at(11, 0))]
+        );
+        assert_eq!(
+            series("cron").await,
+            [(CheckState::NotOk, at(10, 50), at(10, 55))]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Incidents reported by hand through the API or `uptime incident`, shown on the status page and
+//! the pages of the affected websites.
+
+use std::fmt::Write;
+
+use chrono::Utc;
+use eyre::{bail, Result};
+
+use crate::db::{Incident, IncidentStatus, IncidentUpdate, NewIncident, Severity, Website};
+
+/// How long resolved incidents stay on the status page.
+pub(crate) const RESOLVED_HOURS: i64 = 24;
+
+/// Checks a new incident, resolving the slugs or names of the affected websites to their slugs.
+pub fn new_incident(
+    websites: &[Website],
+    title: &str,
+    severity: Severity,
+    affected: &[String],
+    status: IncidentStatus,
+    message: &str,
+) -> Result<NewIncident> {
+    if title.trim().is_empty() {
+        bail!("the title must not be empty");
+    }
+
+    let affected = affected
+        .iter()
+        .map(|name| {
+            websites
+                .iter()
+                .find(|website| &website.slug == name || &website.name == name)
+                .map(|website| website.slug.clone())
+                .ok_or_else(|| eyre::eyre!("unknown website `{name}`"))
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(NewIncident {
+        title: title.to_owned(),
+        severity,
+        websites: affected,
+        update: new_update(status, message),
+    })
+}
+
+/// An update of an incident at the current time.
+pub fn new_update(status: IncidentStatus, message: &str) -> IncidentUpdate {
+    IncidentUpdate {
+        status,
+        message: message.to_owned(),
+        time: Utc::now(),
+    }
+}
+
+/// Formats incidents for terminals, with their updates newest first.
+pub fn render_incidents(incidents: &[Incident]) -> String {
+    let mut out = String::new();
+    for incident in incidents {
+        let _ = write!(
+            out,
+            "#{} {} [{}, {}]",
+            incident.id,
+            incident.title,
+            incident.severity.label(),
+            incident.status().label()
+        );
+        if !incident.websites.is_empty() {
+            let _ = write!(out, " affecting {}", incident.websites.join(", "));
+        }
+        out.push('\n');
+
+        for update in incident.updates.iter().rev() {
+            let time = update
+                .time
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+            let _ = writeln!(
+                out,
+                "  {time} {}: {}",
+                update.status.label(),
+                update.message
+            );
+        }
+    }
+
+    if incidents.is_empty() {
+        out.push_str("No recent incidents\n");
+    }
+
+    out
+}
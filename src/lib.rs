@@ -3,84 +3,444 @@
 #[macro_use]
 extern crate tracing;
 
-mod client;
+mod auth;
+mod badge;
+pub mod client;
 mod config;
 pub mod db;
+pub mod export;
+mod feed;
+pub mod importer;
+pub mod incident;
+mod outage;
+mod retention;
+pub mod site;
+pub mod terminal;
 pub mod web;
 
 use eyre::Context;
 use eyre::Result;
-use sqlx::{Pool, Sqlite};
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashSet, path::PathBuf, sync::Arc, time::Duration};
+use tokio::sync::watch;
 
+pub use auth::hash_password;
 use client::Client;
-pub use config::{read_config, Config, WebsiteConfig};
+pub use config::{
+    json_schema, read_config, Config, GroupConfig, RetentionConfig, StaticSiteConfig, WebsiteConfig,
+};
+use db::Storage;
 pub use web::axum_server;
 
 const USER_AGENT: &str = concat!("github:Nilstrieb/uptime/", env!("GIT_COMMIT"));
 const VERSION: &str = env!("GIT_COMMIT");
 
-pub async fn init() -> Result<(Config, Arc<Pool<Sqlite>>)> {
-    tracing_subscriber::fmt().init();
+/// Which parts of uptime a process runs, so that several read-only web servers can share the
+/// database with a single checker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Role {
+    /// Check websites, apply retention and write the results to the database.
+    Checker,
+    /// Serve the status page, with the database opened read-only.
+    Web,
+    /// Both the checker and the web server.
+    All,
+}
 
-    let version = env!("GIT_COMMIT");
-    info!("Starting up uptime {version}");
+impl Role {
+    fn writes(self) -> bool {
+        self != Role::Web
+    }
+}
+
+/// Where the config is read from, together with the overrides from the command line.
+#[derive(Debug, Clone)]
+pub struct ConfigSource {
+    pub path: String,
+    /// Overrides `db_url` of the config.
+    pub db_url: Option<String>,
+}
 
-    let config_path = std::env::var("UPTIME_CONFIG_PATH").unwrap_or_else(|_| "uptime.json".into());
+impl ConfigSource {
+    /// Reads the config, applying the overrides and `$UPTIME_LISTEN` and `$UPTIME_BASE_PATH`.
+    pub fn load(&self) -> Result<Config> {
+        let mut config = crate::read_config(&self.path)?;
 
-    info!("Loading reading config");
-    let mut config = crate::read_config(&config_path)?;
+        if let Some(db_url) = &self.db_url {
+            config.db_url = db_url.clone();
+        }
+        if let Ok(listen) = std::env::var("UPTIME_LISTEN") {
+            config.web.listen = listen;
+        }
+        if let Ok(base_path) = std::env::var("UPTIME_BASE_PATH") {
+            config.web.base_path = base_path;
+        }
 
-    let db_url = std::env::var("UPTIME_DB_URL");
-    if let Ok(db_url) = db_url {
-        config.db_url = db_url;
+        Ok(config)
     }
+}
+
+pub fn init_logging() {
+    // Logs go to stderr, so that commands can write their output to stdout.
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
+}
+
+/// Sets up logging, loads the config and opens the database, migrating it if `role` writes to it.
+pub async fn init(role: Role, source: &ConfigSource) -> Result<(Config, Arc<dyn Storage>)> {
+    init_logging();
+
+    let version = env!("GIT_COMMIT");
+    info!("Starting up uptime {version}");
+
+    info!("Loading reading config");
+    let mut config = source.load()?;
 
     info!("Opening db");
-    let db = crate::db::open_db(&config.db_url).await?;
-    let db = Arc::new(db);
+    let db = crate::db::open(&config.db_url, !role.writes()).await?;
 
-    info!("Running migrations");
+    if role.writes() {
+        info!("Running migrations");
 
-    crate::db::MIGRATOR
-        .run(&*db)
-        .await
-        .wrap_err("running migrations")?;
+        db.migrate(config.interval_seconds).await?;
 
-    db::migrate_checks(&db, config.interval_seconds)
-        .await
-        .wrap_err("migrating old checks to series")?;
+        db.sync_websites(&mut config.websites)
+            .await
+            .wrap_err("syncing websites")?;
+    } else {
+        info!("Opened db read-only, skipping migrations");
+    }
 
     Ok((config, db))
 }
 
-pub async fn check_timer(config: Config, db: Arc<Pool<Sqlite>>) -> Result<ⵑ> {
-    let req_client = reqwest::Client::builder()
-        .use_rustls_tls()
-        .user_agent(USER_AGENT)
-        .build()
-        .wrap_err("building client")?;
+/// Runs the checker, the web server or both depending on `role`, until one of them fails.
+pub async fn serve(
+    role: Role,
+    source: ConfigSource,
+    config: Config,
+    db: Arc<dyn Storage>,
+) -> Result<()> {
+    info!(?role, "Started up.");
 
-    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_seconds));
+    let retention = config.retention.clone();
+    let (config, config_receiver) = watch::channel(config);
 
-    let client = Client {
-        websites: config.websites,
+    let checker = async {
+        match role {
+            Role::Web => std::future::pending().await,
+            Role::Checker | Role::All => {
+                let (checked, checked_receiver) = watch::channel(());
+                let retention = retention_timer(retention, db.clone());
+                let checker = check_timer(config_receiver.clone(), db.clone(), checked);
+                let static_site =
+                    static_site_generator(config_receiver.clone(), db.clone(), checked_receiver);
+                tokio::select! {
+                    result = checker => result,
+                    result = retention => result,
+                    result = static_site => result,
+                }
+            }
+        }
+    };
+    let server = async {
+        match role {
+            Role::Checker => std::future::pending().await,
+            Role::Web | Role::All => {
+                axum_server(db.clone(), config_receiver.clone(), !role.writes()).await
+            }
+        }
+    };
+    let reloader = config_reloader(source, config, db.clone(), role);
+
+    tokio::select! {
+        result = checker => {
+            result.map(|ok| match ok {})
+        }
+        result = reloader => {
+            result.map(|ok| match ok {})
+        }
+        result = server => {
+            result
+        }
+    }
+}
+
+/// How often the config file is checked for modifications.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Reloads the config whenever the config file or one of its includes is modified or a SIGHUP is
+/// received, publishing it to `config`. Invalid configs are logged and the old one is kept.
+/// Websites are only synced with the database if `role` writes to it.
+pub async fn config_reloader<S: Storage + ?Sized>(
+    source: ConfigSource,
+    config: watch::Sender<Config>,
+    db: Arc<S>,
+    role: Role,
+) -> Result<ⵑ> {
+    let config_path = &source.path;
+    // The included files are globbed again every time, to notice added and removed files.
+    let modified = || {
+        let include = config.borrow().include.clone();
+        let included = config::include_paths(config_path, &include).unwrap_or_default();
+        std::iter::once(PathBuf::from(config_path))
+            .chain(included)
+            .map(|path| {
+                let modified = std::fs::metadata(&path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok();
+                (path, modified)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .wrap_err("listening for SIGHUP")?;
+    let mut interval = tokio::time::interval(CONFIG_POLL_INTERVAL);
+    let mut last_modified = modified();
+
+    loop {
+        tokio::select! {
+            _ = hangup.recv() => {
+                info!("Received SIGHUP, reloading config");
+            }
+            _ = interval.tick() => {
+                let modified = modified();
+                if modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                info!("Config file was modified, reloading config");
+            }
+        }
+
+        let new_config = match source.load() {
+            Ok(mut new_config) if role.writes() => db
+                .sync_websites(&mut new_config.websites)
+                .await
+                .wrap_err("syncing websites")
+                .map(|()| new_config),
+            result => result,
+        };
+
+        match new_config {
+            Ok(new_config) => {
+                {
+                    let old_config = config.borrow();
+                    if new_config.db_url != old_config.db_url
+                        || new_config.retention != old_config.retention
+                        || new_config.web != old_config.web
+                    {
+                        warn!(
+                            "Changes to db_url, retention and web only take effect after a restart"
+                        );
+                    }
+                }
+                config.send_replace(new_config);
+                info!("Reloaded config");
+            }
+            Err(err) => error!(?err, "Invalid config, keeping the old one"),
+        }
+    }
+}
+
+/// Checks all websites every `interval_seconds` and stores the results, notifying `checked` after
+/// every tick.
+pub async fn check_timer<S: Storage + ?Sized>(
+    mut config: watch::Receiver<Config>,
+    db: Arc<S>,
+    checked: watch::Sender<()>,
+) -> Result<ⵑ> {
+    let req_client = http_client()?;
+
+    let mut interval_seconds = config.borrow().interval_seconds;
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_seconds));
+
+    let mut client = Client {
+        websites: Vec::new(),
         req: req_client,
     };
+    let mut managed = Vec::new();
+    let mut paused = HashSet::new();
 
     loop {
         interval.tick().await;
 
+        if config.has_changed().unwrap_or(false) {
+            let config = config.borrow_and_update();
+            if config.interval_seconds != interval_seconds {
+                interval_seconds = config.interval_seconds;
+                let period = Duration::from_secs(interval_seconds);
+                interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            }
+        }
+
         info!("Running tick.");
 
+        // Websites managed through the API and pauses can change at any time, possibly from
+        // another process. Paused websites are still passed on, so that the pause is recorded.
+        match tokio::try_join!(db.get_managed_websites(), db.get_websites()) {
+            Ok((websites, stored)) => {
+                managed = websites;
+                paused = stored
+                    .into_iter()
+                    .filter(|website| website.paused)
+                    .map(|website| website.slug)
+                    .collect();
+            }
+            Err(err) => error!(?err, "Failed to get websites, using the previous ones"),
+        }
+        client.websites = merge_websites(&config.borrow().websites, &managed, &paused)
+            .into_iter()
+            .filter(|website| !website.archived)
+            .collect();
+
         let results = client::do_checks(&client).await;
 
-        if let Err(err) = db::insert_results_series(&db, config.interval_seconds, &results).await {
+        if let Err(err) = db.insert_results_series(interval_seconds, &results).await {
             error!(?err);
         }
+        checked.send_replace(());
         info!("Finished tick.");
     }
 }
 
+/// Generates the static site after every tick of the checker if one is configured. Runs apart from
+/// the checker, so that a slow generation doesn't delay the checks. Ticks that finish while a
+/// generation is running are combined into one.
+pub async fn static_site_generator<S: Storage + ?Sized>(
+    config: watch::Receiver<Config>,
+    db: Arc<S>,
+    mut checked: watch::Receiver<()>,
+) -> Result<ⵑ> {
+    loop {
+        if checked.changed().await.is_err() {
+            // The checker is gone, so there is nothing new to generate.
+            return std::future::pending().await;
+        }
+
+        if config.borrow().static_site.is_none() {
+            continue;
+        }
+        let config = config.borrow().clone();
+        if let Some(static_site) = &config.static_site {
+            info!(output = %static_site.output.display(), "Generating static site.");
+            if let Err(err) = site::generate(&*db, &config, &static_site.output).await {
+                error!(?err, "Failed to generate static site");
+            }
+        }
+    }
+}
+
+fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .use_rustls_tls()
+        .user_agent(USER_AGENT)
+        .build()
+        .wrap_err("building client")
+}
+
+/// The websites of the config followed by the ones managed through the API, with the websites
+/// paused with the CLI or API marked as paused.
+fn merge_websites(
+    configured: &[WebsiteConfig],
+    managed: &[WebsiteConfig],
+    paused: &HashSet<String>,
+) -> Vec<WebsiteConfig> {
+    configured
+        .iter()
+        .chain(managed)
+        .map(|website| {
+            let mut website = website.clone();
+            website.paused |= paused.contains(website.slug());
+            website
+        })
+        .collect()
+}
+
+/// All websites of the config and, if there is a database, the ones managed through the API.
+pub async fn all_websites(config: &Config, db: Option<&dyn Storage>) -> Result<Vec<WebsiteConfig>> {
+    let Some(db) = db else {
+        return Ok(config.websites.clone());
+    };
+
+    let (managed, stored) = tokio::try_join!(db.get_managed_websites(), db.get_websites())?;
+    let paused = stored
+        .into_iter()
+        .filter(|website| website.paused)
+        .map(|website| website.slug)
+        .collect();
+    Ok(merge_websites(&config.websites, &managed, &paused))
+}
+
+/// Checks the websites with the given slugs or names once, even if they are paused, or all websites
+/// that aren't archived or paused if `sites` is empty. The results are not recorded.
+pub async fn check_once(websites: &[WebsiteConfig], sites: &[String]) -> Result<client::Results> {
+    let websites = if sites.is_empty() {
+        websites
+            .iter()
+            .filter(|website| website.is_checked())
+            .cloned()
+            .collect()
+    } else {
+        sites
+            .iter()
+            .map(|site| {
+                websites
+                    .iter()
+                    .find(|website| website.slug() == site || &website.name == site)
+                    .map(|website| {
+                        // Asking for a website explicitly means it should really be checked.
+                        let mut website = website.clone();
+                        website.paused = false;
+                        website
+                    })
+                    .ok_or_else(|| eyre::eyre!("no website `{site}`"))
+            })
+            .collect::<Result<_>>()?
+    };
+
+    let client = Client {
+        websites,
+        req: http_client()?,
+    };
+
+    Ok(client::do_checks(&client).await)
+}
+
+/// Applies the retention policy of the config once.
+pub async fn prune<S: Storage + ?Sized>(config: &Config, db: &S) -> Result<()> {
+    let Some(retention) = &config.retention else {
+        eyre::bail!("no retention is configured, there is nothing to prune");
+    };
+
+    retention::apply_retention(db, retention, chrono::Utc::now()).await
+}
+
+pub async fn retention_timer<S: Storage + ?Sized>(
+    retention: Option<RetentionConfig>,
+    db: Arc<S>,
+) -> Result<ⵑ> {
+    let Some(retention) = retention else {
+        info!("No retention configured, keeping all history");
+        return std::future::pending().await;
+    };
+
+    // Don't run at startup, where we would race with the first check for the database lock.
+    let period = Duration::from_secs(retention.interval_seconds);
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+
+    loop {
+        interval.tick().await;
+
+        info!("Applying retention policy.");
+
+        if let Err(err) = retention::apply_retention(&*db, &retention, chrono::Utc::now()).await {
+            error!(?err);
+        }
+        info!("Finished applying retention policy.");
+    }
+}
+
 // look away
 pub enum ⵑ {}
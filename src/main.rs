@@ -1,52 +1,606 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, IsTerminal, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use chrono::{DateTime, TimeZone, Utc};
+use clap::Parser;
 use eyre::WrapErr;
-use std::sync::Arc;
+use uptime::{
+    client::CheckState,
+    db::{IncidentStatus, Severity, Storage},
+    export::ExportFormat,
+    Config, ConfigSource, Role,
+};
 
 #[macro_use]
 extern crate tracing;
 
+#[derive(Parser)]
+#[command(
+    version = env!("GIT_COMMIT"),
+    about = "custom uptime monitoring tool"
+)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// The config file, in JSON, TOML or YAML.
+    #[arg(
+        long,
+        short,
+        global = true,
+        env = "UPTIME_CONFIG_PATH",
+        default_value = "uptime.json"
+    )]
+    config: String,
+    /// The database URL, overriding `db_url` of the config.
+    #[arg(long, global = true, env = "UPTIME_DB_URL")]
+    db: Option<String>,
+    /// Arguments of `serve`, which runs without a subcommand.
+    #[command(flatten)]
+    serve: ServeArgs,
+}
+
+#[derive(clap::Args)]
+struct ServeArgs {
+    /// Only run the checker or the web server, which opens the database read-only.
+    #[arg(long, value_enum, env = "UPTIME_ROLE", default_value = "all")]
+    role: Role,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Run the checker and web server. This is the default.
+    Serve(ServeArgs),
+    /// Check websites once and print the results. Fails if any check fails.
+    Check {
+        /// The slug or name of a website to check, can be passed multiple times.
+        /// Defaults to all websites that aren't archived or paused, websites passed here are
+        /// checked even if they are paused.
+        #[arg(long = "site")]
+        sites: Vec<String>,
+        #[arg(long, value_enum, default_value = "human")]
+        format: OutputFormat,
+        /// Record the results in the database, like the checker.
+        #[arg(long)]
+        record: bool,
+    },
+    /// Render the status page to stdout or a file, or the whole static site to a directory.
+    Render {
+        /// Only show the most recent part of the history, like `30d`.
+        #[arg(long, value_parser = uptime::web::parse_range)]
+        range: Option<chrono::Duration>,
+        /// Only show websites with this tag.
+        #[arg(long)]
+        tag: Option<String>,
+        /// Where to write the page to, stdout if missing.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Generate the static site with the pages of all websites, badges and feeds instead.
+        /// The directory is replaced by a symlink to the generated site.
+        #[arg(long, conflicts_with_all = ["range", "tag", "output"])]
+        dir: Option<PathBuf>,
+    },
+    /// Print the current state, uptime and history of all websites by group.
+    Status {
+        /// Only show the most recent part of the history, like `30d`.
+        #[arg(long, value_parser = uptime::web::parse_range)]
+        range: Option<chrono::Duration>,
+        /// Only show websites with this tag.
+        #[arg(long)]
+        tag: Option<String>,
+        /// `json` prints the same as `/api/v1/status`.
+        #[arg(long, value_enum, default_value = "human")]
+        format: OutputFormat,
+        #[arg(long, value_enum, default_value = "auto")]
+        color: ColorChoice,
+        /// Keep refreshing the status, every 5 seconds or the given amount of seconds.
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            num_args = 0..=1,
+            default_missing_value = "5",
+            conflicts_with = "format"
+        )]
+        watch: Option<u64>,
+    },
+    /// Write the history of websites to stdout or a file.
+    Export {
+        /// The slug of a website to export, can be passed multiple times.
+        /// Defaults to all configured websites.
+        #[arg(long = "website")]
+        websites: Vec<String>,
+        /// Only export history after this RFC 3339 timestamp.
+        #[arg(long)]
+        from: Option<DateTime<Utc>>,
+        /// Only export history before this RFC 3339 timestamp.
+        #[arg(long)]
+        to: Option<DateTime<Utc>>,
+        #[arg(long, value_enum, default_value = "jsonl")]
+        format: ExportFormat,
+        /// Where to write the export to, stdout if missing.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Merge history exported with `export` into the database.
+    Import {
+        /// The format of the file, guessed from the file extension if missing.
+        #[arg(long, value_enum)]
+        format: Option<ExportFormat>,
+        file: PathBuf,
+    },
+    /// Import the history of another uptime monitoring tool and print config entries for its
+    /// monitors.
+    ImportFrom {
+        #[command(subcommand)]
+        source: ImportSource,
+    },
+    /// Check the config for mistakes without starting anything.
+    Validate {
+        /// Print the JSON Schema of the config instead, for completion in editors.
+        #[arg(long)]
+        schema: bool,
+    },
+    /// Apply the retention policy of the config now.
+    Prune,
+    /// Read a password from stdin and print its hash for `password_hash` of a user.
+    HashPassword,
+    /// Report incidents, which are shown on the status page.
+    Incident {
+        #[command(subcommand)]
+        command: IncidentCommand,
+    },
+    /// Stop checking websites until they are resumed, for example during maintenance.
+    Pause {
+        /// The slugs or names of the websites.
+        #[arg(required = true)]
+        sites: Vec<String>,
+    },
+    /// Check paused websites again.
+    Resume {
+        /// The slugs or names of the websites.
+        #[arg(required = true)]
+        sites: Vec<String>,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum IncidentCommand {
+    /// Report a new incident and print it.
+    Create {
+        #[arg(long)]
+        title: String,
+        #[arg(long, value_enum)]
+        severity: Severity,
+        /// The slug or name of an affected website, can be passed multiple times.
+        #[arg(long = "website")]
+        websites: Vec<String>,
+        #[arg(long, value_enum, default_value = "investigating")]
+        status: IncidentStatus,
+        /// What is known so far.
+        #[arg(long)]
+        message: String,
+    },
+    /// Add an update to the timeline of an incident and print it.
+    Update {
+        /// The id of the incident.
+        id: i64,
+        #[arg(long, value_enum)]
+        status: IncidentStatus,
+        #[arg(long)]
+        message: String,
+    },
+    /// Print incidents that aren't resolved or were updated in the last 30 days.
+    List {
+        #[arg(long, value_enum, default_value = "human")]
+        format: OutputFormat,
+    },
+}
+
+impl Command {
+    /// Commands that only read open the database read-only, like the web server.
+    fn role(&self) -> Role {
+        match self {
+            Command::Serve(serve) => serve.role,
+            Command::Render { .. }
+            | Command::Status { .. }
+            | Command::Export { .. }
+            | Command::Incident {
+                command: IncidentCommand::List { .. },
+            } => Role::Web,
+            _ => Role::All,
+        }
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum OutputFormat {
+    /// A table.
+    Human,
+    /// JSON with an object for every website.
+    Json,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ColorChoice {
+    /// Use colors if stdout is a terminal and `$NO_COLOR` isn't set.
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(clap::Subcommand)]
+enum ImportSource {
+    /// The SQLite database of Uptime Kuma, usually `kuma.db`.
+    UptimeKuma { db: PathBuf },
+    /// A log CSV exported from UptimeRobot.
+    UptimeRobot { file: PathBuf },
+}
+
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
-    tracing_subscriber::fmt().init();
+    let args = Args::parse();
+    let source = ConfigSource {
+        path: args.config,
+        db_url: args.db,
+    };
+    let command = args.command.unwrap_or(Command::Serve(args.serve));
+
+    // These don't need the database.
+    match command {
+        Command::Validate { schema } => {
+            if schema {
+                println!("{}", uptime::json_schema());
+                return Ok(());
+            }
+            source.load()?;
+            println!("{} is valid", source.path);
+            return Ok(());
+        }
+        Command::Check {
+            sites,
+            format,
+            record,
+        } => return check(&source, &sites, format, record).await,
+        Command::HashPassword => {
+            let mut password = String::new();
+            io::stdin()
+                .read_line(&mut password)
+                .wrap_err("reading password")?;
+            let password = password.trim_end_matches(['\n', '\r']);
+            if password.is_empty() {
+                eyre::bail!("the password must not be empty");
+            }
+            println!("{}", uptime::hash_password(password)?);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let (config, db) = uptime::init(command.role(), &source).await?;
+
+    match command {
+        Command::Serve(serve) => uptime::serve(serve.role, source, config, db).await,
+        Command::Render { dir: Some(dir), .. } => uptime::site::generate(&*db, &config, &dir).await,
+        Command::Render {
+            range,
+            tag,
+            output,
+            dir: None,
+        } => {
+            let page = uptime::web::render_root(db, &config, range, tag.as_deref())
+                .await
+                .wrap_err("rendering status page")?;
+
+            match output {
+                Some(output) => std::fs::write(&output, page)
+                    .wrap_err_with(|| format!("writing {}", output.display())),
+                None => write_stdout(page.as_bytes()),
+            }
+        }
+        Command::Status {
+            range,
+            tag,
+            format,
+            color,
+            watch,
+        } => {
+            let tag = tag.as_deref();
+            if let OutputFormat::Json = format {
+                let range = range.unwrap_or_else(|| config.default_range());
+                let status =
+                    uptime::web::status_by_group(&*db, &config.groups, range, tag, true).await?;
+                let status = serde_json::to_string_pretty(&status).wrap_err("encoding status")?;
+                return write_stdout(format!("{status}\n").as_bytes());
+            }
+
+            let color = match color {
+                ColorChoice::Auto => {
+                    io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+                }
+                ColorChoice::Always => true,
+                ColorChoice::Never => false,
+            };
 
-    let version = env!("GIT_COMMIT");
-    info!("Starting up uptime {version}");
+            let Some(watch) = watch else {
+                let status =
+                    uptime::terminal::render_status(&*db, &config, range, tag, color).await?;
+                return write_stdout(status.as_bytes());
+            };
 
-    let config_path = std::env::var("UPTIME_CONFIG_PATH").unwrap_or_else(|_| "uptime.json".into());
+            let mut interval = tokio::time::interval(Duration::from_secs(watch.max(1)));
+            loop {
+                interval.tick().await;
+                let status =
+                    uptime::terminal::render_status(&*db, &config, range, tag, color).await?;
+                // Clear the screen and move the cursor to the top left.
+                let screen = format!(
+                    "\x1b[2J\x1b[H{} (every {watch}s)\n\n{status}",
+                    Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+                );
+                io::stdout()
+                    .lock()
+                    .write_all(screen.as_bytes())
+                    .wrap_err("writing output")?;
+            }
+        }
+        Command::Export {
+            websites,
+            from,
+            to,
+            format,
+            output,
+        } => {
+            let range = from.unwrap_or_else(|| Utc.timestamp_opt(0, 0).unwrap())
+                ..to.unwrap_or_else(Utc::now);
 
-    info!("Loading reading config");
-    let mut config = uptime::read_config(&config_path)?;
+            match output {
+                Some(output) => {
+                    let file = File::create(&output)
+                        .wrap_err_with(|| format!("creating {}", output.display()))?;
+                    uptime::export::export(&*db, websites, range, format, file).await
+                }
+                None => {
+                    uptime::export::export(&*db, websites, range, format, io::stdout().lock()).await
+                }
+            }
+        }
+        Command::Import { format, file } => {
+            let format = format.unwrap_or_else(|| {
+                match file.extension().and_then(|extension| extension.to_str()) {
+                    Some("csv") => ExportFormat::Csv,
+                    _ => ExportFormat::Jsonl,
+                }
+            });
+            let input =
+                File::open(&file).wrap_err_with(|| format!("opening {}", file.display()))?;
+
+            let inserted = uptime::export::import(&*db, format, BufReader::new(input)).await?;
+            info!(inserted, "Imported series");
+
+            Ok(())
+        }
+        Command::ImportFrom { source } => {
+            let imported = match source {
+                ImportSource::UptimeKuma { db: kuma_db } => {
+                    uptime::importer::import_uptime_kuma(&*db, &kuma_db).await?
+                }
+                ImportSource::UptimeRobot { file } => {
+                    let input = File::open(&file)
+                        .wrap_err_with(|| format!("opening {}", file.display()))?;
+                    uptime::importer::import_uptime_robot(&*db, input).await?
+                }
+            };
+            info!(inserted = imported.inserted, "Imported series");
 
-    let db_url = std::env::var("UPTIME_DB_URL");
-    if let Ok(db_url) = db_url {
-        config.db_url = db_url;
+            let websites =
+                serde_json::to_string_pretty(&imported.websites).wrap_err("encoding websites")?;
+            println!("{websites}");
+
+            Ok(())
+        }
+        Command::Prune => uptime::prune(&config, &*db).await,
+        Command::Incident { command } => incident(&*db, command).await,
+        Command::Pause { sites } => pause(&config, &*db, &sites, true).await,
+        Command::Resume { sites } => pause(&config, &*db, &sites, false).await,
+        Command::Check { .. } | Command::Validate { .. } | Command::HashPassword => {
+            unreachable!("handled before opening the database")
+        }
     }
+}
 
-    info!("Opening db");
-    let db = uptime::db::open_db(&config.db_url).await?;
-    let db = Arc::new(db);
+async fn check(
+    source: &ConfigSource,
+    sites: &[String],
+    format: OutputFormat,
+    record: bool,
+) -> eyre::Result<()> {
+    let (config, db) = if record {
+        let (config, db) = uptime::init(Role::All, source).await?;
+        (config, Some(db))
+    } else {
+        uptime::init_logging();
+        let config = source.load()?;
+        // Smoke tests often run without a database, then only the config has websites.
+        let db = match uptime::db::open(&config.db_url, true).await {
+            Ok(db) => Some(db),
+            Err(err) => {
+                info!(
+                    ?err,
+                    "Cannot open the database, only checking websites of the config"
+                );
+                None
+            }
+        };
+        (config, db)
+    };
 
-    info!("Running migrations");
+    let websites = uptime::all_websites(&config, db.as_deref()).await?;
+    let results = uptime::check_once(&websites, sites).await?;
 
-    uptime::db::MIGRATOR
-        .run(&*db)
-        .await
-        .wrap_err("running migrations")?;
+    if let Some(db) = db.filter(|_| record) {
+        db.insert_results_series(config.interval_seconds, &results)
+            .await
+            .wrap_err("recording results")?;
+    }
 
-    uptime::db::migrate_checks(&db, config.interval_seconds)
-        .await
-        .wrap_err("migrating old checks to series")?;
+    let name = |slug: &str| {
+        websites
+            .iter()
+            .find(|website| website.slug() == slug)
+            .map_or(slug, |website| &website.name)
+            .to_owned()
+    };
 
-    info!("Started up.");
+    match format {
+        OutputFormat::Human => {
+            let width = results
+                .states
+                .keys()
+                .map(|slug| name(slug).len())
+                .max()
+                .unwrap_or(0);
+            let mut table = String::new();
+            for (slug, result) in &results.states {
+                let state = match result.state {
+                    CheckState::Ok => "ok",
+                    CheckState::NotOk => "FAILED",
+                    CheckState::Paused => "paused",
+                };
+                let latency = result
+                    .latency_ms
+                    .map(|latency| format!("{latency}ms"))
+                    .unwrap_or_else(|| "-".into());
+                let reason = result.reason.as_deref().unwrap_or_default();
+                let line = format!("{:width$}  {state:6}  {latency:>7}  {reason}", name(slug));
+                table.push_str(line.trim_end());
+                table.push('\n');
+            }
+            write_stdout(table.as_bytes())?;
+        }
+        OutputFormat::Json => {
+            let results = results
+                .states
+                .iter()
+                .map(|(slug, result)| {
+                    serde_json::json!({
+                        "slug": slug,
+                        "name": name(slug),
+                        "time": result.time,
+                        "state": result.state,
+                        "latency_ms": result.latency_ms,
+                        "reason": result.reason,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let results = serde_json::to_string_pretty(&results).wrap_err("encoding results")?;
+            write_stdout(format!("{results}\n").as_bytes())?;
+        }
+    }
 
-    let checker = uptime::check_timer(config, db.clone());
-    let server = uptime::axum_server(db);
+    let failed = results
+        .states
+        .values()
+        .filter(|result| result.state == CheckState::NotOk)
+        .count();
+    if failed > 0 {
+        eyre::bail!("{failed} of {} checks failed", results.states.len());
+    }
+    Ok(())
+}
 
-    tokio::select! {
-        result = checker => {
-            result.map(|ok| match ok {})
+async fn incident(db: &dyn Storage, command: IncidentCommand) -> eyre::Result<()> {
+    let incidents = match command {
+        IncidentCommand::Create {
+            title,
+            severity,
+            websites,
+            status,
+            message,
+        } => {
+            let new = uptime::incident::new_incident(
+                &db.get_websites().await?,
+                &title,
+                severity,
+                &websites,
+                status,
+                &message,
+            )?;
+            let id = db.create_incident(&new).await?;
+            db.get_incident(id).await?.into_iter().collect()
         }
-        result = server => {
-            result
+        IncidentCommand::Update {
+            id,
+            status,
+            message,
+        } => {
+            let update = uptime::incident::new_update(status, &message);
+            if !db.add_incident_update(id, &update).await? {
+                eyre::bail!("there is no incident {id}");
+            }
+            db.get_incident(id).await?.into_iter().collect()
+        }
+        IncidentCommand::List { format } => {
+            let incidents = uptime::web::recent_incidents(db, true).await?;
+            if let OutputFormat::Json = format {
+                let incidents =
+                    serde_json::to_string_pretty(&incidents).wrap_err("encoding incidents")?;
+                return write_stdout(format!("{incidents}\n").as_bytes());
+            }
+            incidents
+        }
+    };
+
+    write_stdout(uptime::incident::render_incidents(&incidents).as_bytes())
+}
+
+/// Pauses or resumes websites, checking all of them before changing any.
+async fn pause(
+    config: &Config,
+    db: &dyn Storage,
+    sites: &[String],
+    paused: bool,
+) -> eyre::Result<()> {
+    let websites = db.get_websites().await?;
+    let websites = sites
+        .iter()
+        .map(|site| {
+            let website = websites
+                .iter()
+                .find(|website| &website.slug == site || &website.name == site)
+                .ok_or_else(|| eyre::eyre!("unknown website `{site}`"))?;
+            let paused_in_config = config
+                .websites
+                .iter()
+                .any(|config| config.slug() == website.slug && config.paused);
+            if !paused && paused_in_config {
+                eyre::bail!(
+                    "website {} is paused in the config, remove `paused` there",
+                    website.name
+                );
+            }
+            Ok(website)
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    let mut out = String::new();
+    for website in websites {
+        db.set_paused(&website.slug, paused).await?;
+        let action = if paused { "Paused" } else { "Resumed" };
+        out.push_str(&format!("{action} {}\n", website.name));
+    }
+    write_stdout(out.as_bytes())
+}
+
+/// Writes to stdout, treating a closed pipe like `uptime render | head` as success.
+fn write_stdout(output: &[u8]) -> eyre::Result<()> {
+    if let Err(io) = io::stdout().lock().write_all(output) {
+        if io.kind() == io::ErrorKind::BrokenPipe {
+            return Ok(());
         }
+        return Err(io).wrap_err("writing output");
     }
+    Ok(())
 }
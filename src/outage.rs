@@ -0,0 +1,84 @@
+//! Outages, the times a website was down, derived from its history.
+
+use std::ops::Range;
+
+use chrono::{DateTime, Utc};
+use eyre::{Context, Result};
+
+use crate::{
+    client::CheckState,
+    db::{Storage, Website},
+};
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Outage {
+    /// The slug of the website.
+    pub website: String,
+    pub name: String,
+    /// The first failed check.
+    pub start: DateTime<Utc>,
+    /// The first check that was OK again, `None` if the website is still down.
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl Outage {
+    /// The last time the outage changed, when it ended or started.
+    pub fn updated(&self) -> DateTime<Utc> {
+        self.end.unwrap_or(self.start)
+    }
+
+    /// How long the outage lasted, like `2h 5m`, or how long it has lasted so far.
+    pub fn duration(&self) -> String {
+        let duration = self.end.unwrap_or_else(Utc::now) - self.start;
+        let (days, hours, minutes) = (
+            duration.num_days(),
+            duration.num_hours() % 24,
+            duration.num_minutes() % 60,
+        );
+        match (days, hours, minutes) {
+            (0, 0, 0) => format!("{}s", duration.num_seconds().max(0)),
+            (0, 0, _) => format!("{minutes}m"),
+            (0, _, _) => format!("{hours}h {minutes}m"),
+            _ => format!("{days}d {hours}h"),
+        }
+    }
+}
+
+/// The outages of a website overlapping with `range`, oldest first. Consecutive failed series are
+/// merged into one outage, even if there is a gap in the history between them.
+/// History that was rolled up by retention is not considered, since it has no exact times.
+pub async fn outages<S: Storage + ?Sized>(
+    db: &S,
+    website: &Website,
+    range: Range<DateTime<Utc>>,
+) -> Result<Vec<Outage>> {
+    let series = db
+        .get_checks_series_in_range(&website.slug, range)
+        .await
+        .wrap_err_with(|| format!("getting history of {}", website.slug))?;
+
+    let mut outages = Vec::new();
+    let mut current = None::<Outage>;
+    for series in series {
+        match series.result {
+            CheckState::NotOk => {
+                current.get_or_insert_with(|| Outage {
+                    website: website.slug.clone(),
+                    name: website.name.clone(),
+                    start: series.request_time_range_start,
+                    end: None,
+                });
+            }
+            // Nothing is known about the website while it is paused, so the outage ends there.
+            CheckState::Ok | CheckState::Paused => {
+                if let Some(mut outage) = current.take() {
+                    outage.end = Some(series.request_time_range_start);
+                    outages.push(outage);
+                }
+            }
+        }
+    }
+    outages.extend(current);
+
+    Ok(outages)
+}
@@ -0,0 +1,116 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use chrono::{DateTime, Utc};
+use eyre::{Context, Result};
+
+use crate::{
+    client::CheckState,
+    config::RetentionConfig,
+    db::{CheckAggregate, CheckSeries, Resolution, Storage},
+};
+
+/// Rolls up and deletes old history according to the retention policy.
+pub async fn apply_retention<S: Storage + ?Sized>(
+    db: &S,
+    config: &RetentionConfig,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let raw_cutoff = days_before(now, config.raw_days)?;
+    let rolled_up = db
+        .rollup_series(raw_cutoff)
+        .await
+        .wrap_err("rolling up series into hourly aggregates")?;
+    info!(rolled_up, "Rolled up old series");
+
+    let hourly_cutoff = days_before(now, config.hourly_days)?;
+    let rolled_up = db
+        .rollup_aggregates(Resolution::Hour, Resolution::Day, hourly_cutoff)
+        .await
+        .wrap_err("rolling up hourly into daily aggregates")?;
+    info!(rolled_up, "Rolled up old hourly aggregates");
+
+    if let Some(delete_after_days) = config.delete_after_days {
+        let delete_cutoff = days_before(now, delete_after_days)?;
+        db.delete_before(delete_cutoff)
+            .await
+            .wrap_err("deleting expired history")?;
+    }
+
+    Ok(())
+}
+
+fn days_before(now: DateTime<Utc>, days: u64) -> Result<DateTime<Utc>> {
+    days.checked_mul(24 * 60 * 60)
+        .and_then(|secs| chrono::Duration::from_std(Duration::from_secs(secs)).ok())
+        .and_then(|duration| now.checked_sub_signed(duration))
+        .ok_or_else(|| eyre::eyre!("retention of {days} days is too long"))
+}
+
+/// Splits series into hourly buckets.
+///
+/// The time a series spans is distributed onto all buckets it overlaps with, its latency
+/// summary is attributed to the bucket it started in.
+pub fn aggregate_series(series: &[CheckSeries]) -> Vec<CheckAggregate> {
+    let resolution = Resolution::Hour;
+    let mut buckets = BTreeMap::new();
+
+    for series in series {
+        let start = series.request_time_range_start;
+        let end = series.request_time_range_end;
+
+        let mut bucket_start = resolution.bucket_start(start);
+        let mut first = true;
+        loop {
+            let aggregate = buckets
+                .entry((series.website.clone(), bucket_start))
+                .or_insert_with(|| CheckAggregate::new(&series.website, resolution, bucket_start));
+            let bucket_end = aggregate.bucket_end();
+
+            let overlap_millis = (std::cmp::min(end, bucket_end)
+                - std::cmp::max(start, bucket_start))
+            .num_milliseconds()
+            .max(0);
+
+            match series.result {
+                CheckState::Ok => {
+                    aggregate.ok_millis += overlap_millis;
+                    aggregate.ok_series += 1;
+                }
+                CheckState::NotOk => {
+                    aggregate.not_ok_millis += overlap_millis;
+                    aggregate.not_ok_series += 1;
+                }
+                CheckState::Paused => {
+                    aggregate.paused_millis += overlap_millis;
+                    aggregate.paused_series += 1;
+                }
+            }
+            if first {
+                aggregate.latency.merge(&series.latency);
+                first = false;
+            }
+
+            if end <= bucket_end {
+                break;
+            }
+            bucket_start = bucket_end;
+        }
+    }
+
+    buckets.into_values().collect()
+}
+
+/// Merges aggregates into buckets of a coarser resolution.
+pub fn aggregate_aggregates(aggregates: &[CheckAggregate], to: Resolution) -> Vec<CheckAggregate> {
+    let mut buckets = BTreeMap::new();
+
+    for aggregate in aggregates {
+        let bucket_start = to.bucket_start(aggregate.bucket_start);
+        buckets
+            .entry((aggregate.website.clone(), bucket_start))
+            .or_insert_with(|| CheckAggregate::new(&aggregate.website, to, bucket_start))
+            .merge(aggregate);
+    }
+
+    buckets.into_values().collect()
+}
@@ -0,0 +1,313 @@
+//! Generates the status page as a directory of static files, for static hosting.
+//!
+//! ```text
+//! index.html                     the status page
+//! tags/{tag}/index.html          the status page of a tag
+//! websites/{slug}/index.html     the page of a website
+//! websites/{slug}/feed.atom      the outages and announcements of a website, also as feed.rss
+//! badge/{slug}/status.svg        the latest state of a website
+//! badge/{slug}/uptime.svg        the uptime of a website over the last 30 days
+//! badge/{slug}/response-time.svg the average response time over the last 30 days
+//! feed.atom                      the outages and announcements of all websites, also as feed.rss
+//! api/v1/status.json             the same as `/api/v1/status`
+//! api/v1/incidents.json          the same as `/api/v1/incidents`
+//! ```
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use eyre::{bail, Context, Result};
+
+use crate::{
+    badge::{self, Badge},
+    config::Config,
+    db::Storage,
+    feed::{self, FeedFormat},
+    web::{self, path_segment},
+};
+
+/// Generates the site into a new directory next to `output` and then atomically points `output`
+/// to it, so that a web server never serves a half-written site.
+/// The pages are rendered in memory first and written on a blocking thread, so that the files don't
+/// hold up the runtime.
+pub async fn generate<S: Storage + ?Sized>(db: &S, config: &Config, output: &Path) -> Result<()> {
+    let files = render_site(db, config).await?;
+
+    let output = output.to_owned();
+    tokio::task::spawn_blocking(move || write_generation(&output, files))
+        .await
+        .wrap_err("writing static site")?
+}
+
+fn write_generation(output: &Path, files: Vec<(String, String)>) -> Result<()> {
+    let (parent, name) = split_output(output)?;
+    let generation = parent.join(format!(".{name}-{}", Utc::now().timestamp_nanos()));
+
+    fs::create_dir_all(&generation)
+        .wrap_err_with(|| format!("creating {}", generation.display()))?;
+
+    let result = files
+        .into_iter()
+        .try_for_each(|(path, contents)| write(&generation, &path, contents))
+        .and_then(|()| replace(output, &generation));
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&generation);
+    }
+    result
+}
+
+/// Every file of the site with its path. The site is served to anyone, so private websites are left
+/// out.
+async fn render_site<S: Storage + ?Sized>(
+    db: &S,
+    config: &Config,
+) -> Result<Vec<(String, String)>> {
+    let mut files = Vec::new();
+    let websites = web::visible_websites(db, false).await?;
+
+    let index = web::render_index(db, config, None, None, false, String::new()).await?;
+    files.push(("index.html".into(), index));
+
+    let status =
+        web::status_by_group(db, &config.groups, config.default_range(), None, false).await?;
+    let status = serde_json::to_string(&status).wrap_err("encoding status")?;
+    files.push(("api/v1/status.json".into(), status));
+
+    let incidents = web::recent_incidents(db, false).await?;
+    let incidents = serde_json::to_string(&incidents).wrap_err("encoding incidents")?;
+    files.push(("api/v1/incidents.json".into(), incidents));
+
+    let tags = websites
+        .iter()
+        .flat_map(|website| website.tags.iter())
+        .collect::<BTreeSet<_>>();
+    for tag in tags {
+        let page = web::render_index(db, config, None, Some(tag), false, "../../".into()).await?;
+        files.push((format!("tags/{}/index.html", path_segment(tag)), page));
+    }
+
+    for website in websites {
+        let segment = path_segment(&website.slug);
+
+        for badge in Badge::ALL {
+            let range = chrono::Duration::days(badge::DEFAULT_DAYS);
+            let svg = badge::render(db, website.clone(), badge, range).await?;
+            files.push((format!("badge/{segment}/{}", badge.file_name()), svg));
+        }
+
+        for format in FeedFormat::ALL {
+            let base_url = feed::base_url(config, "../../");
+            let feed = feed::render(db, config, Some(&website), format, false, &base_url).await?;
+            files.push((format!("websites/{segment}/{}", format.file_name()), feed));
+        }
+
+        let page = web::render_website(db, config, website, false, "../../".into()).await?;
+        files.push((format!("websites/{segment}/index.html"), page));
+    }
+
+    for format in FeedFormat::ALL {
+        let base_url = feed::base_url(config, "");
+        let feed = feed::render(db, config, None, format, false, &base_url).await?;
+        files.push((format.file_name().into(), feed));
+    }
+
+    Ok(files)
+}
+
+fn write(dir: &Path, path: &str, contents: String) -> Result<()> {
+    let path = dir.join(path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).wrap_err_with(|| format!("creating {}", parent.display()))?;
+    }
+    fs::write(&path, contents).wrap_err_with(|| format!("writing {}", path.display()))
+}
+
+/// The directory `output` is in and its file name.
+fn split_output(output: &Path) -> Result<(PathBuf, String)> {
+    let Some(name) = output.file_name().and_then(|name| name.to_str()) else {
+        bail!("invalid output directory {}", output.display());
+    };
+    let parent = match output.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_owned(),
+        _ => PathBuf::from("."),
+    };
+    Ok((parent, name.to_owned()))
+}
+
+/// Points the symlink at `output` to `generation`, replacing it atomically with a rename, and
+/// deletes the generation it pointed to before.
+fn replace(output: &Path, generation: &Path) -> Result<()> {
+    let (parent, name) = split_output(output)?;
+
+    let previous = match fs::symlink_metadata(output) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            Some(fs::read_link(output).wrap_err_with(|| format!("reading {}", output.display()))?)
+        }
+        Ok(_) => bail!(
+            "{} is not a symlink, remove it so that it can be replaced by one",
+            output.display()
+        ),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err).wrap_err_with(|| format!("reading {}", output.display())),
+    };
+
+    // The link is relative, so that the whole directory can be moved.
+    let target = generation.file_name().expect("generation has a file name");
+    let link = parent.join(format!(".{name}.tmp"));
+    let _ = fs::remove_file(&link);
+    std::os::unix::fs::symlink(target, &link)
+        .wrap_err_with(|| format!("creating symlink {}", link.display()))?;
+    fs::rename(&link, output).wrap_err_with(|| format!("replacing {}", output.display()))?;
+
+    // Only delete previous generations, not directories the link was pointed to by hand.
+    if let Some(previous) = previous {
+        let is_generation = previous
+            .parent()
+            .map_or(true, |dir| dir.as_os_str().is_empty())
+            && previous
+                .to_str()
+                .is_some_and(|previous| previous.starts_with(&format!(".{name}-")));
+        if is_generation && previous != target {
+            let previous = parent.join(previous);
+            if let Err(err) = fs::remove_dir_all(&previous) {
+                warn!(?err, path = %previous.display(), "Failed to delete old generation");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::write_generation;
+
+    /// An empty directory for the test `name`.
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("uptime-site-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// The names of all entries of `dir`, sorted.
+    fn entries(dir: &PathBuf) -> Vec<String> {
+        let mut entries = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        entries.sort();
+        entries
+    }
+
+    fn site(index: &str) -> Vec<(String, String)> {
+        vec![
+            ("index.html".into(), index.into()),
+            ("api/v1/status.json".into(), "[]".into()),
+        ]
+    }
+
+    #[test]
+    fn generations_replace_each_other() {
+        let dir = dir("replace");
+        let output = dir.join("site");
+        fs::create_dir(dir.join(".site-stale")).unwrap();
+
+        write_generation(&output, site("first")).unwrap();
+        let first = fs::read_link(&output).unwrap();
+        assert!(first.is_relative());
+        assert!(first.to_str().unwrap().starts_with(".site-"));
+        assert_eq!(
+            fs::read_to_string(output.join("index.html")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            fs::read_to_string(output.join("api/v1/status.json")).unwrap(),
+            "[]"
+        );
+
+        write_generation(&output, site("second")).unwrap();
+        let second = fs::read_link(&output).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(
+            fs::read_to_string(output.join("index.html")).unwrap(),
+            "second"
+        );
+        // Only the generation the link pointed to is deleted.
+        assert!(!dir.join(&first).exists());
+        assert_eq!(
+            entries(&dir),
+            [second.to_str().unwrap(), ".site-stale", "site"]
+        );
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn directories_linked_by_hand_are_kept() {
+        let dir = dir("by_hand");
+        let output = dir.join("site");
+        fs::create_dir(dir.join("manual")).unwrap();
+        fs::write(dir.join("manual/index.html"), "manual").unwrap();
+        std::os::unix::fs::symlink("manual", &output).unwrap();
+
+        write_generation(&output, site("generated")).unwrap();
+        assert_eq!(
+            fs::read_to_string(output.join("index.html")).unwrap(),
+            "generated"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("manual/index.html")).unwrap(),
+            "manual"
+        );
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn existing_directories_are_not_replaced() {
+        let dir = dir("not_symlink");
+        let output = dir.join("site");
+        fs::create_dir(&output).unwrap();
+        fs::write(output.join("index.html"), "mine").unwrap();
+
+        let err = write_generation(&output, site("generated")).unwrap_err();
+        assert!(err.to_string().contains("is not a symlink"), "{err}");
+        assert_eq!(
+            fs::read_to_string(output.join("index.html")).unwrap(),
+            "mine"
+        );
+        // The new generation is cleaned up again.
+        assert_eq!(entries(&dir), ["site"]);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn failed_writes_remove_the_generation() {
+        let dir = dir("failed_write");
+        let output = dir.join("site");
+        write_generation(&output, site("first")).unwrap();
+        let first = fs::read_link(&output).unwrap();
+
+        // `index.html` is a file, so nothing can be written below it.
+        let mut broken = site("second");
+        broken.push(("index.html/nested".into(), String::new()));
+        assert!(write_generation(&output, broken).is_err());
+
+        assert_eq!(fs::read_link(&output).unwrap(), first);
+        assert_eq!(
+            fs::read_to_string(output.join("index.html")).unwrap(),
+            "first"
+        );
+        assert_eq!(entries(&dir), [first.to_str().unwrap(), "site"]);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
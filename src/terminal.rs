@@ -0,0 +1,138 @@
+//! The status of all websites for terminals, shown by `uptime status`.
+
+use std::fmt::Write;
+
+use chrono::Duration;
+use eyre::Result;
+
+use crate::{
+    client::CheckState,
+    config::Config,
+    db::Storage,
+    web::{self, BarClass, GroupState, UPTIME_WINDOWS},
+};
+
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+const RED: &str = "31";
+const BLUE: &str = "34";
+const DIM: &str = "2";
+const BOLD: &str = "1";
+
+/// Renders the status of all websites by group with their uptime over the last day, week and
+/// month, followed by a sparkline of the history in `range`, or the default range of the config
+/// if no range is given.
+pub async fn render_status<S: Storage + ?Sized>(
+    db: &S,
+    config: &Config,
+    range: Option<Duration>,
+    tag: Option<&str>,
+    color: bool,
+) -> Result<String> {
+    let range = range.unwrap_or_else(|| config.default_range());
+    let groups = web::status_by_group(db, &config.groups, range, tag, true).await?;
+
+    let paint = |text: &str, code: &str| {
+        if color {
+            format!("\x1b[{code}m{text}\x1b[0m")
+        } else {
+            text.to_owned()
+        }
+    };
+
+    let width = groups
+        .iter()
+        .flat_map(|group| &group.websites)
+        .map(|website| website.website.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut out = String::new();
+    for group in &groups {
+        let indent = if let Some(name) = &group.name {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            let state = match group.state {
+                GroupState::Up => paint(group.state.label(), GREEN),
+                GroupState::Degraded => paint(group.state.label(), YELLOW),
+                GroupState::Down => paint(group.state.label(), RED),
+                GroupState::Unknown => paint(group.state.label(), DIM),
+            };
+            let _ = writeln!(out, "{}  {state}", paint(name, BOLD));
+            "  "
+        } else {
+            ""
+        };
+
+        for website in &group.websites {
+            // Pad before painting, the escape codes would count towards the width.
+            let state = match (website.archived, website.current) {
+                (true, _) => paint(&format!("{:8}", "archived"), DIM),
+                (false, Some(CheckState::Ok)) => paint(&format!("{:8}", "up"), GREEN),
+                (false, Some(CheckState::NotOk)) => paint(&format!("{:8}", "down"), RED),
+                (false, Some(CheckState::Paused)) => paint(&format!("{:8}", "paused"), BLUE),
+                (false, None) => paint(&format!("{:8}", "unknown"), DIM),
+            };
+            let _ = write!(out, "{indent}{:width$}  {state}", website.website);
+
+            let bounds = db.get_history_bounds(&website.slug).await?;
+            for (label, days) in UPTIME_WINDOWS {
+                let uptime = match &bounds {
+                    Some(bounds) => {
+                        let range = web::clamp_range(bounds.clone(), Duration::days(days));
+                        web::uptime_in(db, &website.slug, range).await?
+                    }
+                    None => None,
+                };
+                let uptime = match uptime {
+                    Some(uptime) => format!("{:.2}%", uptime * 100.0),
+                    None => "-".to_owned(),
+                };
+                let _ = write!(out, "  {label} {uptime:>7}");
+            }
+
+            let last_ok = website.last_ok.as_deref().unwrap_or("never");
+            let _ = writeln!(out, "  {}", paint(&format!("last ok {last_ok}"), DIM));
+
+            let _ = writeln!(out, "{indent}{}", sparkline(&website.bar_info.elems, paint));
+        }
+    }
+
+    if groups.is_empty() {
+        out.push_str("No websites have any history yet\n");
+    }
+
+    Ok(out)
+}
+
+/// Draws the bar of the status page with block characters, which are lower the worse a bin was,
+/// so that it can be read without colors as well.
+fn sparkline(elems: &[BarClass], paint: impl Fn(&str, &str) -> String) -> String {
+    let mut line = String::new();
+    let mut run = String::new();
+    let mut run_class = None;
+
+    for elem in elems {
+        let (char, code) = match elem {
+            BarClass::Green => ('█', GREEN),
+            BarClass::Orange => ('▄', YELLOW),
+            BarClass::Red => ('▁', RED),
+            BarClass::Paused => ('-', BLUE),
+            BarClass::Unknown => ('·', DIM),
+        };
+        if run_class != Some(code) {
+            if let Some(run_class) = run_class {
+                line.push_str(&paint(&run, run_class));
+            }
+            run.clear();
+            run_class = Some(code);
+        }
+        run.push(char);
+    }
+    if let Some(run_class) = run_class {
+        line.push_str(&paint(&run, run_class));
+    }
+
+    line
+}
@@ -1,18 +1,39 @@
-use std::{collections::BTreeMap, ops::Range, sync::Arc};
+use std::{
+    collections::BTreeMap, ops::Range, os::unix::fs::FileTypeExt, pin::Pin, sync::Arc, task::Poll,
+};
 
 use askama::Template;
 use axum::{
-    extract::State,
-    response::{Html, IntoResponse, Response},
-    routing::get,
-    Router,
+    body::StreamBody,
+    extract::{Path, Query, State},
+    middleware,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post, put},
+    Extension, Json, Router,
 };
-use chrono::{DateTime, Utc};
-use eyre::{Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use eyre::{eyre, Context, Result};
+use futures_util::StreamExt;
 use http::StatusCode;
-use sqlx::{Pool, Sqlite};
+use tokio::sync::watch;
+
+use crate::{
+    auth::{self, Identity, Peer},
+    badge::{self, Badge},
+    client::CheckState,
+    config::{Config, GroupConfig, ListenAddr, Scope, WebsiteConfig},
+    db::{Bin, Incident, IncidentStatus, IncidentUpdate, SaveWebsite, Severity, Storage, Website},
+    export::ExportFormat,
+    feed::{self, FeedFormat},
+    incident,
+    outage::{self, Outage},
+};
 
-use crate::{client::CheckState, db::CheckSeries};
+/// The windows the uptime of a website is shown for, in days.
+pub(crate) const UPTIME_WINDOWS: [(&str, i64); 3] = [("24h", 1), ("7d", 7), ("30d", 30)];
+
+/// How far back outages are listed on the page of a website.
+pub(crate) const OUTAGE_DAYS: i64 = 30;
 
 trait RenderDate {
     fn render_nicely(&self) -> String;
@@ -24,35 +45,916 @@ impl RenderDate for chrono::DateTime<Utc> {
     }
 }
 
-pub async fn axum_server(db: Arc<Pool<Sqlite>>) -> Result<()> {
-    let app = Router::new().route("/", get(root)).with_state(db);
+/// Everything the handlers need, the config is kept up to date by the reloader.
+struct AppState<S: ?Sized> {
+    db: Arc<S>,
+    config: watch::Receiver<Config>,
+    /// Whether the database was opened read-only by the web role.
+    read_only: bool,
+}
 
-    info!("Serving website on port 3000");
+impl<S: ?Sized> Clone for AppState<S> {
+    fn clone(&self) -> Self {
+        Self {
+            db: self.db.clone(),
+            config: self.config.clone(),
+            read_only: self.read_only,
+        }
+    }
+}
 
-    axum::Server::bind(&"0.0.0.0:3000".parse().unwrap())
-        .serve(app.into_make_service())
-        .await
-        .wrap_err("running axum server")
+pub async fn axum_server<S: Storage + ?Sized + 'static>(
+    db: Arc<S>,
+    config: watch::Receiver<Config>,
+    read_only: bool,
+) -> Result<()> {
+    let web = config.borrow().web.clone();
+    let base_path = web.base_path();
+
+    let mut app = Router::new()
+        .route(&format!("{base_path}/"), get(root::<S>))
+        .route(&format!("{base_path}/tags/:tag/"), get(tag::<S>))
+        .route(
+            &format!("{base_path}/websites/:website/"),
+            get(website::<S>),
+        )
+        .route(
+            &format!("{base_path}/websites/:website/:feed"),
+            get(website_feed::<S>),
+        )
+        .route(
+            &format!("{base_path}/badge/:website/:badge"),
+            get(badge::<S>),
+        )
+        .route(&format!("{base_path}/api/v1/status"), get(status::<S>))
+        .route(&format!("{base_path}/api/v1/export"), get(export::<S>))
+        .route(
+            &format!("{base_path}/api/v1/incidents"),
+            get(incidents::<S>).post(create_incident::<S>),
+        )
+        .route(
+            &format!("{base_path}/api/v1/incidents/:incident/updates"),
+            post(update_incident::<S>),
+        )
+        .route(
+            &format!("{base_path}/api/v1/websites"),
+            get(managed_websites::<S>).post(add_website::<S>),
+        )
+        .route(
+            &format!("{base_path}/api/v1/websites/:website"),
+            put(put_website::<S>).delete(delete_website::<S>),
+        )
+        .route(
+            &format!("{base_path}/api/v1/websites/:website/pause"),
+            post(|state, identity, path| set_paused(state, identity, path, true)),
+        )
+        .route(
+            &format!("{base_path}/api/v1/websites/:website/resume"),
+            post(|state, identity, path| set_paused(state, identity, path, false)),
+        );
+    for format in FeedFormat::ALL {
+        app = app.route(
+            &format!("{base_path}/{}", format.file_name()),
+            get(move |state, identity| feed(state, identity, format)),
+        );
+    }
+    // Reverse proxies forward the base path both with and without the trailing slash.
+    if !base_path.is_empty() {
+        app = app.route(&base_path, get(root::<S>));
+    }
+    let app = app
+        .route(&format!("{base_path}/login"), get(login::<S>))
+        .layer(middleware::from_fn_with_state(
+            config.clone(),
+            auth::authenticate,
+        ))
+        .with_state(AppState {
+            db,
+            config,
+            read_only,
+        });
+
+    match web.listen_addr()? {
+        ListenAddr::Tcp(addr) => {
+            info!(%addr, base_path, "Serving website");
+
+            axum::Server::try_bind(&addr)
+                .wrap_err_with(|| format!("binding to {addr}"))?
+                .serve(app.into_make_service_with_connect_info::<Peer>())
+                .await
+                .wrap_err("running axum server")
+        }
+        ListenAddr::Unix(path) => {
+            // A socket left over from a previous run would make binding fail, but anything else
+            // at that path is not ours to delete.
+            match std::fs::symlink_metadata(&path) {
+                Ok(metadata) if metadata.file_type().is_socket() => {
+                    std::fs::remove_file(&path)
+                        .wrap_err_with(|| format!("removing old socket {}", path.display()))?;
+                }
+                Ok(_) => {
+                    return Err(eyre!(
+                        "{} already exists and is not a socket",
+                        path.display()
+                    ));
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    return Err(err)
+                        .wrap_err_with(|| format!("checking for old socket {}", path.display()));
+                }
+            }
+            let listener = tokio::net::UnixListener::bind(&path)
+                .wrap_err_with(|| format!("binding to {}", path.display()))?;
+
+            info!(path = %path.display(), base_path, "Serving website");
+
+            axum::Server::builder(UnixAccept(listener))
+                .serve(app.into_make_service_with_connect_info::<Peer>())
+                .await
+                .wrap_err("running axum server")
+        }
+    }
+}
+
+/// Accepts connections on a Unix domain socket for hyper.
+struct UnixAccept(tokio::net::UnixListener);
+
+impl hyper::server::accept::Accept for UnixAccept {
+    type Conn = tokio::net::UnixStream;
+    type Error = std::io::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        self.0
+            .poll_accept(cx)
+            .map(|result| Some(result.map(|(stream, _)| stream)))
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct RootQuery {
+    range: Option<String>,
+    /// Only show websites with this tag.
+    tag: Option<String>,
+}
+
+async fn root<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+    Query(query): Query<RootQuery>,
+) -> Response {
+    let range = match query.range.as_deref().map(parse_range).transpose() {
+        Ok(range) => range,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+    let config = state.config.borrow().clone();
+
+    let base_path = format!("{}/", config.web.base_path());
+    let tag = query.tag.as_deref();
+    render_index(
+        &*state.db,
+        &config,
+        range,
+        tag,
+        identity.private(),
+        base_path,
+    )
+    .await
+    .map(Html)
+    .map(IntoResponse::into_response)
+    .unwrap_or_else(|err| {
+        error!(?err);
+        (StatusCode::INTERNAL_SERVER_ERROR).into_response()
+    })
+}
+
+/// The status page for a single tag, the same as `?tag=`, but with a path that works for the
+/// static site as well.
+async fn tag<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+    Path(segment): Path<String>,
+) -> Response {
+    let result = async {
+        let websites = state.db.get_websites().await?;
+        let Some(tag) = websites
+            .into_iter()
+            .filter(|website| identity.can_see(website))
+            .flat_map(|website| website.tags.0)
+            .find(|tag| path_segment(tag) == segment)
+        else {
+            return Ok(None);
+        };
+
+        let config = state.config.borrow().clone();
+        let base_path = format!("{}/", config.web.base_path());
+        let private = identity.private();
+        render_index(&*state.db, &config, None, Some(&tag), private, base_path)
+            .await
+            .map(Some)
+    };
+
+    page_response(result.await)
+}
+
+/// The page of a single website with its uptime and outages.
+async fn website<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+    Path(segment): Path<String>,
+) -> Response {
+    let result = async {
+        let Some(website) = find_website(&*state.db, &identity, &segment).await? else {
+            return Ok(None);
+        };
+
+        let config = state.config.borrow().clone();
+        let base_path = format!("{}/", config.web.base_path());
+        render_website(&*state.db, &config, website, identity.private(), base_path)
+            .await
+            .map(Some)
+    };
+
+    page_response(result.await)
+}
+
+/// The outages and announcements of all websites.
+async fn feed<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+    format: FeedFormat,
+) -> Response {
+    let config = state.config.borrow().clone();
+    let base_url = feed::base_url(&config, &format!("{}/", config.web.base_path()));
+
+    let private = identity.private();
+    let result = feed::render(&*state.db, &config, None, format, private, &base_url).await;
+    feed_response(format, result.map(Some))
 }
 
-async fn root(State(db): State<Arc<Pool<Sqlite>>>) -> Response {
-    render_root(db)
+/// The outages and announcements of a single website.
+async fn website_feed<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+    Path((segment, file_name)): Path<(String, String)>,
+) -> Response {
+    let Some(format) = FeedFormat::from_file_name(&file_name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let result = async {
+        let Some(website) = find_website(&*state.db, &identity, &segment).await? else {
+            return Ok(None);
+        };
+
+        let config = state.config.borrow().clone();
+        let base_url = feed::base_url(&config, &format!("{}/", config.web.base_path()));
+        let private = identity.private();
+        feed::render(
+            &*state.db,
+            &config,
+            Some(&website),
+            format,
+            private,
+            &base_url,
+        )
         .await
-        .map(Html)
-        .map(IntoResponse::into_response)
-        .unwrap_or_else(|err| {
+        .map(Some)
+    };
+
+    feed_response(format, result.await)
+}
+
+fn feed_response(format: FeedFormat, feed: Result<Option<String>>) -> Response {
+    match feed {
+        Ok(Some(feed)) => {
+            ([(http::header::CONTENT_TYPE, format.content_type())], feed).into_response()
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            error!(?err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct BadgeQuery {
+    /// How much of the history the uptime and response time cover, 30 days by default.
+    range: Option<String>,
+}
+
+/// A shields.io style badge of a website, like `/badge/{website}/uptime.svg?range=7d`.
+async fn badge<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+    Path((segment, file_name)): Path<(String, String)>,
+    Query(query): Query<BadgeQuery>,
+) -> Response {
+    let range = match query.range.as_deref().map(parse_range).transpose() {
+        Ok(range) => range.unwrap_or_else(|| chrono::Duration::days(badge::DEFAULT_DAYS)),
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+    let Some(kind) = Badge::from_file_name(&file_name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let result = async {
+        let Some(website) = find_website(&*state.db, &identity, &segment).await? else {
+            return Ok(None);
+        };
+        let public = website.public;
+        badge::render(&*state.db, website, kind, range)
+            .await
+            .map(|svg| Some((svg, public)))
+    };
+
+    match result.await {
+        Ok(Some((svg, public))) => {
+            // The badge only changes after the next check. Shared caches must not hand badges of
+            // private websites to others.
+            let max_age = state.config.borrow().interval_seconds;
+            let cache = if public { "public" } else { "private" };
+            (
+                [
+                    (http::header::CONTENT_TYPE, "image/svg+xml".to_owned()),
+                    (
+                        http::header::CACHE_CONTROL,
+                        format!("{cache}, max-age={max_age}"),
+                    ),
+                ],
+                svg,
+            )
+                .into_response()
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            error!(?err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// The website with the path segment `segment`, if `identity` can see it.
+async fn find_website<S: Storage + ?Sized>(
+    db: &S,
+    identity: &Identity,
+    segment: &str,
+) -> Result<Option<Website>> {
+    Ok(db
+        .get_websites()
+        .await?
+        .into_iter()
+        .filter(|website| identity.can_see(website))
+        .find(|website| path_segment(&website.slug) == segment))
+}
+
+/// Asks browsers for the credentials of a user, which they then send along with every request.
+async fn login<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+) -> Response {
+    let config = state.config.borrow().clone();
+    if config.auth.users.is_empty() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    match identity.name {
+        Some(_) => Redirect::to(&format!("{}/", config.web.base_path())).into_response(),
+        None => auth::challenge(&config.auth),
+    }
+}
+
+fn page_response(page: Result<Option<String>>) -> Response {
+    match page {
+        Ok(Some(page)) => Html(page).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            error!(?err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// The status of all websites by group, the same as shown on the status page.
+async fn status<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+    Query(query): Query<RootQuery>,
+) -> Response {
+    let range = match query.range.as_deref().map(parse_range).transpose() {
+        Ok(range) => range,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+    let (groups, default_range) = {
+        let config = state.config.borrow();
+        (config.groups.clone(), config.default_range())
+    };
+    let range = range.unwrap_or(default_range);
+
+    let tag = query.tag.as_deref();
+    match status_by_group(&*state.db, &groups, range, tag, identity.private()).await {
+        Ok(status) => Json(status).into_response(),
+        Err(err) => {
+            error!(?err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct ExportQuery {
+    /// Comma separated slugs, all configured websites if missing.
+    websites: Option<String>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    format: Option<ExportFormat>,
+}
+
+/// Streams the series of the requested websites as they are fetched, one website at a time.
+async fn export<S: Storage + ?Sized + 'static>(
+    State(AppState { db, .. }): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let websites = query
+        .websites
+        .map(|websites| websites.split(',').map(ToOwned::to_owned).collect())
+        .unwrap_or_default();
+    let websites = match crate::export::export_websites(&*db, websites, identity.private()).await {
+        Ok(websites) => websites,
+        Err(err) => {
+            error!(?err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let range = query
+        .from
+        .unwrap_or_else(|| Utc.timestamp_opt(0, 0).unwrap())
+        ..query.to.unwrap_or_else(Utc::now);
+    let format = query.format.unwrap_or(ExportFormat::Jsonl);
+
+    // The export borrows the database, so it runs in its own task and hands over the chunks.
+    let (sender, receiver) = tokio::sync::mpsc::channel(64);
+    tokio::spawn(async move {
+        let mut chunks =
+            std::pin::pin!(crate::export::export_stream(&*db, &websites, range, format));
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(|err| {
+                error!(?err);
+                std::io::Error::new(std::io::ErrorKind::Other, "failed to export series")
+            });
+            let failed = chunk.is_err();
+            if sender.send(chunk).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    let chunks = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        let chunk = receiver.recv().await?;
+        Some((chunk, receiver))
+    });
+
+    (
+        [(http::header::CONTENT_TYPE, format.content_type())],
+        StreamBody::new(chunks),
+    )
+        .into_response()
+}
+
+/// Incidents that aren't resolved or were updated in the last `OUTAGE_DAYS`.
+async fn incidents<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+) -> Response {
+    match recent_incidents(&*state.db, identity.private()).await {
+        Ok(incidents) => Json(incidents).into_response(),
+        Err(err) => {
+            error!(?err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct CreateIncident {
+    title: String,
+    severity: Severity,
+    /// Slugs or names of the affected websites.
+    #[serde(default)]
+    websites: Vec<String>,
+    #[serde(default = "investigating")]
+    status: IncidentStatus,
+    message: String,
+}
+
+fn investigating() -> IncidentStatus {
+    IncidentStatus::Investigating
+}
+
+/// Reports a new incident, responding with it.
+async fn create_incident<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+    Json(request): Json<CreateIncident>,
+) -> Response {
+    if let Err(response) = authorize_write(&state, &identity, Scope::Incidents) {
+        return response;
+    }
+
+    let websites = match state.db.get_websites().await {
+        Ok(mut websites) => {
+            websites.retain(|website| identity.can_see(website));
+            websites
+        }
+        Err(err) => {
+            error!(?err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let new = match incident::new_incident(
+        &websites,
+        &request.title,
+        request.severity,
+        &request.websites,
+        request.status,
+        &request.message,
+    ) {
+        Ok(new) => new,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+
+    let result = async {
+        let id = state.db.create_incident(&new).await?;
+        info!(
+            id,
+            title = new.title,
+            by = identity.name,
+            "Created incident"
+        );
+        state.db.get_incident(id).await
+    };
+
+    match result.await {
+        Ok(Some(incident)) => (StatusCode::CREATED, Json(incident)).into_response(),
+        Ok(None) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Err(err) => {
+            error!(?err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct UpdateIncident {
+    status: IncidentStatus,
+    message: String,
+}
+
+/// Adds an update to the timeline of an incident, responding with the incident.
+async fn update_incident<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+    Path(id): Path<i64>,
+    Json(request): Json<UpdateIncident>,
+) -> Response {
+    if let Err(response) = authorize_write(&state, &identity, Scope::Incidents) {
+        return response;
+    }
+
+    let update = incident::new_update(request.status, &request.message);
+    let result = async {
+        if !state.db.add_incident_update(id, &update).await? {
+            return Ok(None);
+        }
+        info!(id, status = ?update.status, by = identity.name, "Updated incident");
+        state.db.get_incident(id).await
+    };
+
+    match result.await {
+        Ok(Some(incident)) => Json(incident).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            error!(?err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// All websites managed through the API.
+async fn managed_websites<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+) -> Response {
+    if let Err(response) = identity.require(Scope::Websites) {
+        return response;
+    }
+
+    match state.db.get_managed_websites().await {
+        Ok(websites) => Json(websites).into_response(),
+        Err(err) => {
+            error!(?err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Adds a website, its slug defaults to its name. Fails if the slug is taken.
+async fn add_website<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+    Json(mut website): Json<WebsiteConfig>,
+) -> Response {
+    if let Err(response) = authorize_write(&state, &identity, Scope::Websites) {
+        return response;
+    }
+
+    website.slug.get_or_insert_with(|| website.name.clone());
+    save_website(&state, &identity, website, false).await
+}
+
+/// Adds or replaces the website with the slug `website`.
+async fn put_website<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+    Path(slug): Path<String>,
+    Json(mut website): Json<WebsiteConfig>,
+) -> Response {
+    if let Err(response) = authorize_write(&state, &identity, Scope::Websites) {
+        return response;
+    }
+
+    if website.slug.as_ref().is_some_and(|body| *body != slug) {
+        return (
+            StatusCode::BAD_REQUEST,
+            "slug: must be the same as in the path",
+        )
+            .into_response();
+    }
+    website.slug = Some(slug);
+    save_website(&state, &identity, website, true).await
+}
+
+/// Validates and stores a website managed through the API, responding with it. With `replace`,
+/// a website managed through the API with the same slug is replaced, otherwise it is a conflict.
+async fn save_website<S: Storage + ?Sized>(
+    state: &AppState<S>,
+    identity: &Identity,
+    website: WebsiteConfig,
+    replace: bool,
+) -> Response {
+    let slug = website.slug().to_owned();
+
+    let mut problems = website.url_problems();
+    if website.name.trim().is_empty() {
+        problems.push("name: must not be empty".to_owned());
+    }
+    if slug.is_empty() {
+        problems.push("slug: must not be empty".to_owned());
+    }
+    if !website.previous_names.is_empty() {
+        problems.push(
+            "previous_names: not supported for websites managed through the API, keep the slug instead"
+                .to_owned(),
+        );
+    }
+    if !problems.is_empty() {
+        return (StatusCode::BAD_REQUEST, problems.join("\n")).into_response();
+    }
+
+    // Only decides between 200 and 201, the storage decides whether the website can be saved.
+    let existed = if replace {
+        match state.db.get_managed_websites().await {
+            Ok(managed) => managed.iter().any(|managed| managed.slug() == slug),
+            Err(err) => {
+                error!(?err);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    } else {
+        false
+    };
+
+    let saved = if replace {
+        state.db.upsert_managed_website(&website).await
+    } else {
+        state.db.create_managed_website(&website).await
+    };
+    match saved {
+        Ok(SaveWebsite::Saved) => {
+            info!(slug, by = identity.name, "Saved website");
+            let status = if existed {
+                StatusCode::OK
+            } else {
+                StatusCode::CREATED
+            };
+            (status, Json(website)).into_response()
+        }
+        Ok(SaveWebsite::SlugTaken) if replace => (
+            StatusCode::CONFLICT,
+            format!("the website `{slug}` is defined in the config"),
+        )
+            .into_response(),
+        Ok(SaveWebsite::SlugTaken) => (
+            StatusCode::CONFLICT,
+            format!("there already is a website with the slug `{slug}`"),
+        )
+            .into_response(),
+        Ok(SaveWebsite::NameTaken) => (
+            StatusCode::CONFLICT,
+            format!(
+                "the name `{}` is already used by another website",
+                website.name
+            ),
+        )
+            .into_response(),
+        Err(err) => {
+            error!(?err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Deletes a website managed through the API, keeping its history.
+async fn delete_website<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+    Path(slug): Path<String>,
+) -> Response {
+    if let Err(response) = authorize_write(&state, &identity, Scope::Websites) {
+        return response;
+    }
+
+    match state.db.delete_managed_website(&slug).await {
+        Ok(true) => {
+            info!(slug, by = identity.name, "Deleted website");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => {
+            let configured = state
+                .config
+                .borrow()
+                .websites
+                .iter()
+                .any(|website| website.slug() == slug);
+            if configured {
+                (
+                    StatusCode::CONFLICT,
+                    format!("the website `{slug}` is defined in the config"),
+                )
+                    .into_response()
+            } else {
+                StatusCode::NOT_FOUND.into_response()
+            }
+        }
+        Err(err) => {
             error!(?err);
-            (StatusCode::INTERNAL_SERVER_ERROR).into_response()
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Pauses or resumes a website from the config or managed through the API.
+async fn set_paused<S: Storage + ?Sized>(
+    State(state): State<AppState<S>>,
+    Extension(identity): Extension<Identity>,
+    Path(slug): Path<String>,
+    paused: bool,
+) -> Response {
+    if let Err(response) = authorize_write(&state, &identity, Scope::Websites) {
+        return response;
+    }
+
+    let paused_in_config = state
+        .config
+        .borrow()
+        .websites
+        .iter()
+        .any(|website| website.slug() == slug && website.paused);
+    if !paused && paused_in_config {
+        return (
+            StatusCode::CONFLICT,
+            format!("the website `{slug}` is paused in the config"),
+        )
+            .into_response();
+    }
+
+    match state.db.set_paused(&slug, paused).await {
+        Ok(true) => {
+            if paused {
+                info!(slug, by = identity.name, "Paused website");
+            } else {
+                info!(slug, by = identity.name, "Resumed website");
+            }
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            error!(?err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Checks that the request has `scope` and that the database is writable.
+fn authorize_write<S: ?Sized>(
+    state: &AppState<S>,
+    identity: &Identity,
+    scope: Scope,
+) -> Result<(), Response> {
+    identity.require(scope)?;
+
+    if state.read_only {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "the database is read-only in the web role",
+        )
+            .into_response());
+    }
+
+    Ok(())
+}
+
+/// Parses a time range like `90m`, `12h` or `30d`.
+pub fn parse_range(range: &str) -> Result<chrono::Duration> {
+    let unit_idx = range
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| eyre::eyre!("range `{range}` is missing a unit (m, h, d)"))?;
+    let (amount, unit) = range.split_at(unit_idx);
+    let amount = amount
+        .parse::<u64>()
+        .wrap_err_with(|| format!("invalid range `{range}`"))?;
+    if amount == 0 {
+        return Err(eyre::eyre!("range `{range}` is empty"));
+    }
+
+    let unit_seconds = match unit {
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => {
+            return Err(eyre::eyre!(
+                "invalid unit `{unit}` in range, expected m, h or d"
+            ))
+        }
+    };
+    amount
+        .checked_mul(unit_seconds)
+        .and_then(|seconds| {
+            chrono::Duration::from_std(std::time::Duration::from_secs(seconds)).ok()
         })
+        .ok_or_else(|| eyre::eyre!("range `{range}` is too long"))
+}
+
+/// Renders the status page. Only the last `range` of each website's history is shown, or the
+/// default range of the config if no range is given. If `tag` is given, only websites with that
+/// tag are shown. Private websites are left out, like for anonymous visitors.
+pub async fn render_root<S: Storage + ?Sized>(
+    db: Arc<S>,
+    config: &Config,
+    range: Option<chrono::Duration>,
+    tag: Option<&str>,
+) -> Result<String> {
+    let base_path = format!("{}/", config.web.base_path());
+    render_index(&*db, config, range, tag, false, base_path).await
 }
 
-pub async fn render_root(db: Arc<Pool<Sqlite>>) -> Result<String> {
-    let checks = crate::db::get_checks_series(&db).await?;
+/// Renders the status page with all links relative to `base_path`, with private websites if
+/// `private` is set.
+pub(crate) async fn render_index<S: Storage + ?Sized>(
+    db: &S,
+    config: &Config,
+    range: Option<chrono::Duration>,
+    tag: Option<&str>,
+    private: bool,
+    base_path: String,
+) -> Result<String> {
+    let range = range.unwrap_or_else(|| config.default_range());
+    let groups = status_by_group(db, &config.groups, range, tag, private).await?;
 
-    let status = compute_status(checks);
+    let websites = visible_websites(db, private).await?;
+    let since = Utc::now() - chrono::Duration::hours(incident::RESOLVED_HOURS);
+    let incidents = incidents_since(db, since, private)
+        .await?
+        .into_iter()
+        .filter(|incident| {
+            // With a tag, only incidents about shown websites are relevant.
+            incident.websites.is_empty()
+                || groups
+                    .iter()
+                    .flat_map(|group| &group.websites)
+                    .any(|website| incident.websites.contains(&website.slug))
+        })
+        .map(|incident| IncidentView::new(incident, &websites))
+        .collect();
 
     let html = RootTemplate {
-        status,
+        groups,
+        incidents,
+        tag: tag.map(ToOwned::to_owned),
+        base_path,
         version: crate::VERSION,
     }
     .render()
@@ -60,61 +962,273 @@ pub async fn render_root(db: Arc<Pool<Sqlite>>) -> Result<String> {
     Ok(html)
 }
 
-fn compute_status(checks: Vec<CheckSeries>) -> Vec<WebsiteStatus> {
-    let mut websites = BTreeMap::new();
+/// Renders the page of a single website with all links relative to `base_path`.
+pub(crate) async fn render_website<S: Storage + ?Sized>(
+    db: &S,
+    config: &Config,
+    website: Website,
+    private: bool,
+    base_path: String,
+) -> Result<String> {
+    let url = match config
+        .websites
+        .iter()
+        .find(|config| config.slug() == website.slug)
+    {
+        Some(config) => Some(config.url.to_string()),
+        None if website.managed => db
+            .get_managed_websites()
+            .await?
+            .into_iter()
+            .find(|managed| managed.slug() == website.slug)
+            .map(|managed| managed.url.to_string()),
+        None => None,
+    };
 
-    checks.into_iter().for_each(|check| {
-        websites.entry(check.website).or_insert(Vec::new()).push((
-            check.request_time_range_start..check.request_time_range_end,
-            check.result,
-        ));
-    });
+    let mut windows = Vec::new();
+    let mut outages = Vec::new();
+    if let Some(bounds) = db.get_history_bounds(&website.slug).await? {
+        for (label, days) in UPTIME_WINDOWS {
+            let range = clamp_range(bounds.clone(), chrono::Duration::days(days));
+            let uptime = uptime_in(db, &website.slug, range).await?;
+            windows.push((label, format_uptime(uptime)));
+        }
 
-    websites
+        let range = clamp_range(bounds, chrono::Duration::days(OUTAGE_DAYS));
+        outages = outage::outages(db, &website, range).await?;
+        outages.reverse();
+    }
+
+    let websites = visible_websites(db, private).await?;
+    let incidents = recent_incidents(db, private)
+        .await?
         .into_iter()
-        .map(|(website, mut checks)| {
-            checks.sort_by_key(|check| check.0.start);
-
-            let mut last_ok = None;
-            let mut count_ok = 0;
-
-            const BAR_ELEMS: usize = 100;
-            let bar_info = checks_to_classes(&checks, BAR_ELEMS);
-
-            let total_duration_millis = checks
-                .first()
-                .map(|first| {
-                    checks.last().unwrap().0.end.timestamp_millis()
-                        - first.0.start.timestamp_millis()
-                })
-                .unwrap_or_default();
-
-            checks.into_iter().for_each(|(time, result)| {
-                if let CheckState::Ok = result {
-                    last_ok = std::cmp::max(last_ok, Some(time.end));
-                    count_ok += time.end.timestamp_millis() - time.start.timestamp_millis();
-                }
-            });
+        .filter(|incident| incident.websites.contains(&website.slug))
+        .map(|incident| IncidentView::new(incident, &websites))
+        .collect();
+
+    let html = WebsiteTemplate {
+        slug: website.slug.clone(),
+        name: website.name.clone(),
+        archived: website.archived,
+        tags: website.tags.0.clone(),
+        url,
+        status: website_status(db, website, config.default_range()).await?,
+        windows,
+        outages,
+        incidents,
+        outage_days: OUTAGE_DAYS,
+        base_path,
+        badges: Badge::ALL,
+        version: crate::VERSION,
+    }
+    .render()
+    .wrap_err("error rendering template")?;
+    Ok(html)
+}
+
+/// Incidents that aren't resolved or were updated in the last `OUTAGE_DAYS`, like in
+/// `/api/v1/incidents`.
+pub async fn recent_incidents<S: Storage + ?Sized>(db: &S, private: bool) -> Result<Vec<Incident>> {
+    incidents_since(
+        db,
+        Utc::now() - chrono::Duration::days(OUTAGE_DAYS),
+        private,
+    )
+    .await
+}
+
+/// Incidents that aren't resolved or were updated after `since`. Without `private`, incidents
+/// that are only about private websites are left out, and so are the private websites of the
+/// others.
+async fn incidents_since<S: Storage + ?Sized>(
+    db: &S,
+    since: DateTime<Utc>,
+    private: bool,
+) -> Result<Vec<Incident>> {
+    let mut incidents = db.get_incidents(since).await?;
+    if private {
+        return Ok(incidents);
+    }
+
+    let hidden = db
+        .get_websites()
+        .await?
+        .into_iter()
+        .filter(|website| !website.public)
+        .map(|website| website.slug)
+        .collect::<Vec<_>>();
+    incidents.retain_mut(|incident| {
+        let general = incident.websites.is_empty();
+        incident.websites.retain(|slug| !hidden.contains(slug));
+        general || !incident.websites.is_empty()
+    });
+
+    Ok(incidents)
+}
+
+/// All websites, without the private ones unless `private` is set.
+pub(crate) async fn visible_websites<S: Storage + ?Sized>(
+    db: &S,
+    private: bool,
+) -> Result<Vec<Website>> {
+    let mut websites = db.get_websites().await?;
+    websites.retain(|website| private || website.public);
+    Ok(websites)
+}
+
+/// The status of all websites by group, like on the status page and in `/api/v1/status`.
+/// Private websites are only included if `private` is set.
+pub async fn status_by_group<S: Storage + ?Sized>(
+    db: &S,
+    groups: &[GroupConfig],
+    range: chrono::Duration,
+    tag: Option<&str>,
+    private: bool,
+) -> Result<Vec<GroupStatus>> {
+    let status = compute_status(db, range, tag, private).await?;
+    Ok(group_status(groups, status))
+}
+
+async fn compute_status<S: Storage + ?Sized>(
+    db: &S,
+    range: chrono::Duration,
+    tag: Option<&str>,
+    private: bool,
+) -> Result<Vec<WebsiteStatus>> {
+    let mut status = Vec::new();
+
+    for website in visible_websites(db, private).await? {
+        if let Some(tag) = tag {
+            if !website.tags.iter().any(|website_tag| website_tag == tag) {
+                continue;
+            }
+        }
+
+        if let Some(website) = website_status(db, website, range).await? {
+            status.push(website);
+        }
+    }
+
+    Ok(status)
+}
+
+/// The status of a single website, `None` if it has no history yet.
+pub(crate) async fn website_status<S: Storage + ?Sized>(
+    db: &S,
+    website: Website,
+    range: chrono::Duration,
+) -> Result<Option<WebsiteStatus>> {
+    let Some(bounds) = db.get_history_bounds(&website.slug).await? else {
+        return Ok(None);
+    };
+    let range = clamp_range(bounds, range);
+
+    const BAR_ELEMS: usize = 100;
+    let bins = db.get_bins(&website.slug, range.clone(), BAR_ELEMS).await?;
+    let bar_info = checks_to_classes(&bins, range.clone());
+
+    let uptime = uptime_in(db, &website.slug, range).await?;
+    let ok_ratio = format_uptime(uptime);
+
+    let last_ok = db.get_last_ok(&website.slug).await?;
+    let last_ok = last_ok.map(|utc| utc.render_nicely());
+
+    let current = db
+        .get_latest_series_for_website(&website.slug)
+        .await?
+        .map(|series| series.result);
+
+    Ok(Some(WebsiteStatus {
+        slug: website.slug,
+        website: website.name,
+        archived: website.archived,
+        group: website.group,
+        tags: website.tags.0,
+        current,
+        last_ok,
+        uptime,
+        ok_ratio,
+        bar_info,
+    }))
+}
+
+/// The last `range` of the history, or all of it if it is shorter.
+pub(crate) fn clamp_range(
+    bounds: Range<DateTime<Utc>>,
+    range: chrono::Duration,
+) -> Range<DateTime<Utc>> {
+    match bounds.end.checked_sub_signed(range) {
+        Some(start) => std::cmp::max(bounds.start, start)..bounds.end,
+        None => bounds,
+    }
+}
+
+/// The ratio of time a website was OK within `range`, not counting the time it was paused.
+/// `None` if it was paused the whole time.
+pub(crate) async fn uptime_in<S: Storage + ?Sized>(
+    db: &S,
+    website: &str,
+    range: Range<DateTime<Utc>>,
+) -> Result<Option<f32>> {
+    let total_duration_millis = (range.end - range.start).num_milliseconds();
+    let millis = db.get_state_millis(website, range).await?;
 
-            let ok_ratio = (count_ok as f32) / (total_duration_millis as f32);
-            let ok_ratio = format!("{:.2}%", ok_ratio * 100.0);
+    // Paused time is neither up nor down, so it doesn't count at all.
+    let checked_millis = total_duration_millis - millis.paused_millis;
+    if checked_millis <= 0 {
+        return Ok(None);
+    }
+    Ok(Some((millis.ok_millis as f32) / (checked_millis as f32)))
+}
+
+/// Formats an uptime as a percentage, `-` if it is unknown.
+fn format_uptime(uptime: Option<f32>) -> String {
+    match uptime {
+        Some(uptime) => format!("{:.2}%", uptime * 100.0),
+        None => "-".to_owned(),
+    }
+}
 
-            let last_ok = last_ok.map(|utc| utc.render_nicely());
-            WebsiteStatus {
-                website,
-                last_ok,
-                ok_ratio,
-                bar_info,
+/// Sorts the websites into their groups, ordered by the `order` of the groups and then their name.
+/// Websites without a group come first, in a group without a name.
+fn group_status(groups: &[GroupConfig], status: Vec<WebsiteStatus>) -> Vec<GroupStatus> {
+    let mut grouped = BTreeMap::<Option<String>, Vec<WebsiteStatus>>::new();
+    for website in status {
+        grouped
+            .entry(website.group.clone())
+            .or_default()
+            .push(website);
+    }
+
+    let mut groups = grouped
+        .into_iter()
+        .map(|(name, websites)| {
+            let config = groups
+                .iter()
+                .find(|group| Some(&group.name) == name.as_ref());
+            GroupStatus {
+                state: GroupState::of(&websites),
+                description: config.and_then(|group| group.description.clone()),
+                order: config.map_or(0, |group| group.order),
+                name,
+                websites,
             }
         })
-        .collect()
+        .collect::<Vec<_>>();
+    groups.sort_by(|a, b| {
+        (a.name.is_some(), a.order, &a.name).cmp(&(b.name.is_some(), b.order, &b.name))
+    });
+
+    groups
 }
 
 #[derive(Debug)]
-enum BarClass {
+pub(crate) enum BarClass {
     Green,
     Orange,
     Red,
+    Paused,
     Unknown,
 }
 
@@ -124,79 +1238,34 @@ impl BarClass {
             Self::Green => "check-result-green",
             Self::Orange => "check-result-orange",
             Self::Red => "check-result-red",
+            Self::Paused => "check-result-paused",
             Self::Unknown => "check-result-unknown",
         }
     }
 }
 
 #[derive(Debug)]
-struct BarInfo {
-    elems: Vec<BarClass>,
+pub(crate) struct BarInfo {
+    pub(crate) elems: Vec<BarClass>,
     first_time: Option<DateTime<Utc>>,
     last_time: Option<DateTime<Utc>>,
 }
 
-/// Converts a list of (sorted by time) checks at arbitrary dates into a list of boxes for the
-/// frontend, in a fixed sensical timeline.
-/// We slice the time from the first check to the last check (maybe something like last check-30d
-/// in the future) into slices and aggregate all checks from these times into these slices.
-fn checks_to_classes(
-    checks_series: &[(Range<DateTime<Utc>>, CheckState)],
-    classes: usize,
-) -> BarInfo {
-    assert_ne!(classes, 0);
-    let Some(first) = checks_series.first() else {
-        return BarInfo {
-            elems: Vec::new(),
-            first_time: None,
-            last_time: None,
-        };
-    };
-    let last = checks_series.last().unwrap();
-
-    let mut bins = vec![vec![]; classes];
-
-    let first_event = first.0.start.timestamp_millis() as f64; // welcome to float land, where we float
-    let last_event = last.0.end.timestamp_millis() as f64;
-
-    let event_time_range = last_event - first_event;
-    assert!(
-        event_time_range.is_sign_positive(),
-        "checks not ordered correctly"
-    );
-
-    let bin_diff = event_time_range / (classes as f64);
-
-    let bin_ranges = (0..classes).map(|i| {
-        // we DO NOT want to miss the last event due to imprecision, so widen the range for the last event
-        let end_factor_range = if i == (classes - 1) { 2.0 } else { 1.0 };
-        let i = i as f64;
-        (i * bin_diff)..((i + end_factor_range) * bin_diff)
-    });
-
-    for series in checks_series {
-        for (i, bin_range) in bin_ranges.clone().enumerate() {
-            let start = (series.0.start.timestamp_millis() as f64) - first_event;
-            let end = (series.0.end.timestamp_millis() as f64) - first_event;
-            assert!(start.is_sign_positive(), "checks not ordered correctly");
-            assert!(end.is_sign_positive(), "checks not ordered correctly");
-
-            if !range_disjoint(bin_range, start..end) {
-                bins[i].push(series);
-            }
-        }
-    }
-
+/// Converts the bins of a website's history into a list of boxes for the frontend, in a fixed
+/// sensical timeline.
+/// The bins are computed by the database, slicing the time from the first check to the last check
+/// (maybe something like last check-30d in the future) into slices and aggregating all checks from
+/// these times into these slices.
+fn checks_to_classes(bins: &[Bin], range: Range<DateTime<Utc>>) -> BarInfo {
     let elems = bins
         .iter()
-        .map(|checks| {
-            let ok = checks
-                .iter()
-                .filter(|check| check.1 == CheckState::Ok)
-                .count();
-            let all = checks.len();
+        .map(|bin| {
+            let ok = bin.ok_series;
+            let all = bin.ok_series + bin.not_ok_series;
 
-            if all == 0 {
+            if all == 0 && bin.paused_series > 0 {
+                BarClass::Paused
+            } else if all == 0 {
                 BarClass::Unknown
             } else if all == ok {
                 BarClass::Green
@@ -212,26 +1281,209 @@ fn checks_to_classes(
 
     BarInfo {
         elems,
-        first_time: Some(first.0.start),
-        last_time: Some(last.0.end),
+        first_time: Some(range.start),
+        last_time: Some(range.end),
     }
 }
 
-fn range_disjoint<T: PartialOrd>(a: Range<T>, b: Range<T>) -> bool {
-    (a.end < b.start) || (a.start > b.end)
+#[derive(Debug, serde::Serialize)]
+pub struct WebsiteStatus {
+    pub(crate) slug: String,
+    pub(crate) website: String,
+    pub(crate) archived: bool,
+    group: Option<String>,
+    tags: Vec<String>,
+    /// The result of the latest check.
+    pub(crate) current: Option<CheckState>,
+    pub(crate) last_ok: Option<String>,
+    /// The ratio of time the website was OK in the shown range, `None` if it was paused the
+    /// whole time.
+    pub(crate) uptime: Option<f32>,
+    #[serde(skip)]
+    ok_ratio: String,
+    #[serde(skip)]
+    pub(crate) bar_info: BarInfo,
+}
+
+impl WebsiteStatus {
+    pub(crate) fn is_paused(&self) -> bool {
+        self.current == Some(CheckState::Paused)
+    }
 }
 
-#[derive(Debug)]
-struct WebsiteStatus {
-    website: String,
-    last_ok: Option<String>,
-    ok_ratio: String,
-    bar_info: BarInfo,
+/// The state of a group, summarizing the latest checks of all websites in it that aren't archived or
+/// paused.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum GroupState {
+    Up,
+    Degraded,
+    Down,
+    Unknown,
+}
+
+impl GroupState {
+    fn of(websites: &[WebsiteStatus]) -> Self {
+        let current = websites
+            .iter()
+            .filter(|website| !website.archived)
+            .filter_map(|website| website.current)
+            .filter(|state| *state != CheckState::Paused)
+            .collect::<Vec<_>>();
+
+        if current.is_empty() {
+            Self::Unknown
+        } else if current.iter().all(|state| *state == CheckState::Ok) {
+            Self::Up
+        } else if current.iter().all(|state| *state == CheckState::NotOk) {
+            Self::Down
+        } else {
+            Self::Degraded
+        }
+    }
+
+    fn as_class(&self) -> &'static str {
+        match self {
+            Self::Up => "group-state-up",
+            Self::Degraded => "group-state-degraded",
+            Self::Down => "group-state-down",
+            Self::Unknown => "group-state-unknown",
+        }
+    }
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            Self::Up => "all up",
+            Self::Degraded => "partially down",
+            Self::Down => "down",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct GroupStatus {
+    /// `None` for websites without a group.
+    pub(crate) name: Option<String>,
+    description: Option<String>,
+    #[serde(skip)]
+    order: i64,
+    pub(crate) state: GroupState,
+    pub(crate) websites: Vec<WebsiteStatus>,
+}
+
+#[derive(Template)]
+#[template(path = "website.html")]
+struct WebsiteTemplate {
+    slug: String,
+    name: String,
+    archived: bool,
+    tags: Vec<String>,
+    /// The URL from the config, missing if the website isn't configured anymore.
+    url: Option<String>,
+    /// `None` if the website has no history yet.
+    status: Option<WebsiteStatus>,
+    /// The uptime over every window in `UPTIME_WINDOWS`.
+    windows: Vec<(&'static str, String)>,
+    /// The outages of the last `outage_days`, newest first.
+    outages: Vec<Outage>,
+    incidents: Vec<IncidentView>,
+    outage_days: i64,
+    base_path: String,
+    badges: [Badge; 3],
+    version: &'static str,
+}
+
+/// The path segment of the pages of a website or tag. Slugs and tags can contain anything, so
+/// slashes and leading dots are escaped to keep every page in its own directory.
+pub(crate) fn path_segment(name: &str) -> String {
+    let segment = name.replace('%', "%25").replace('/', "%2F");
+    match segment.strip_prefix('.') {
+        Some(rest) => format!("%2E{rest}"),
+        None => segment,
+    }
+}
+
+mod filters {
+    pub fn path_segment<T: std::fmt::Display>(name: T) -> askama::Result<String> {
+        Ok(super::path_segment(&name.to_string()))
+    }
+}
+
+/// An incident as shown on the pages.
+struct IncidentView {
+    title: String,
+    severity: Severity,
+    status: IncidentStatus,
+    /// The slugs and names of the affected websites.
+    websites: Vec<(String, String)>,
+    /// Newest first.
+    updates: Vec<IncidentUpdate>,
+}
+
+impl IncidentView {
+    fn new(incident: Incident, websites: &[Website]) -> Self {
+        let status = incident.status();
+        let mut updates = incident.updates;
+        updates.reverse();
+
+        Self {
+            title: incident.title,
+            severity: incident.severity,
+            status,
+            // Websites that were removed from the config since are left out.
+            websites: incident
+                .websites
+                .iter()
+                .filter_map(|slug| websites.iter().find(|website| &website.slug == slug))
+                .map(|website| (website.slug.clone(), website.name.clone()))
+                .collect(),
+            updates,
+        }
+    }
 }
 
 #[derive(Template)]
 #[template(path = "index.html")]
 struct RootTemplate {
-    status: Vec<WebsiteStatus>,
+    groups: Vec<GroupStatus>,
+    incidents: Vec<IncidentView>,
+    tag: Option<String>,
+    /// The path of the status page, all links are relative to it.
+    /// This is a relative path like `../../` on the pages of the static site.
+    base_path: String,
     version: &'static str,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse_range;
+
+    #[test]
+    fn parse_range_units() {
+        assert_eq!(parse_range("90m").unwrap(), chrono::Duration::minutes(90));
+        assert_eq!(parse_range("12h").unwrap(), chrono::Duration::hours(12));
+        assert_eq!(parse_range("30d").unwrap(), chrono::Duration::days(30));
+    }
+
+    #[test]
+    fn parse_range_rejects_invalid_ranges() {
+        let error = |range| parse_range(range).unwrap_err().to_string();
+
+        assert_eq!(error("0h"), "range `0h` is empty");
+        assert_eq!(error("5x"), "invalid unit `x` in range, expected m, h or d");
+        assert_eq!(error("12"), "range `12` is missing a unit (m, h, d)");
+        assert_eq!(error(""), "range `` is missing a unit (m, h, d)");
+        assert_eq!(error("h"), "invalid range `h`");
+
+        // Overflows the seconds and the range chrono can represent, respectively.
+        let overflow = format!("{}d", u64::MAX / 86400 + 1);
+        assert_eq!(error(&overflow), format!("range `{overflow}` is too long"));
+        assert_eq!(error("200000000000d"), "range `200000000000d` is too long");
+        let too_many_digits = format!("{}0d", u64::MAX);
+        assert_eq!(
+            error(&too_many_digits),
+            format!("invalid range `{too_many_digits}`")
+        );
+    }
+}